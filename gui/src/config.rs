@@ -0,0 +1,670 @@
+use bevy::prelude::{ButtonInput, KeyCode, Resource};
+use serde::de::{self, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::tetrust::{Ruleset, NEXT_MAX};
+
+// 設定ファイル
+// CLI版(tetrust)と同じ設定ファイルを共有する
+// 省略した項目はデフォルト値を使う
+#[derive(Resource, Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub keys: Bindings,
+    pub handling: Handling,
+    pub visual: Visual,
+    pub rules: ModeRules,
+}
+
+// キーに割り当てる操作
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    HardDrop,
+    #[serde(rename = "rotate_cw", alias = "rotate_right")]
+    RotateCW,
+    #[serde(rename = "rotate_ccw", alias = "rotate_left")]
+    RotateCCW,
+    #[serde(rename = "rotate_180")]
+    Rotate180,
+    Hold,
+    Pause,
+    Restart,
+    // 端末版の練習モードのみで使う(同じ設定ファイルを読めるように受け付ける)
+    Undo,
+    Redo,
+    Quit,
+}
+
+// すべての操作(キー割り当ての画面に表示する順)
+pub const ACTIONS: [Action; 13] = [
+    Action::MoveLeft,
+    Action::MoveRight,
+    Action::SoftDrop,
+    Action::HardDrop,
+    Action::RotateCW,
+    Action::RotateCCW,
+    Action::Rotate180,
+    Action::Hold,
+    Action::Pause,
+    Action::Restart,
+    Action::Undo,
+    Action::Redo,
+    Action::Quit,
+];
+
+impl Action {
+    // 設定ファイルでの名前
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::SoftDrop => "soft_drop",
+            Action::HardDrop => "hard_drop",
+            Action::RotateCW => "rotate_cw",
+            Action::RotateCCW => "rotate_ccw",
+            Action::Rotate180 => "rotate_180",
+            Action::Hold => "hold",
+            Action::Pause => "pause",
+            Action::Restart => "restart",
+            Action::Undo => "undo",
+            Action::Redo => "redo",
+            Action::Quit => "quit",
+        }
+    }
+
+    // ブロックに対する操作か(一時停止などゲームの外側の操作ではないか)
+    pub fn is_operation(self) -> bool {
+        !matches!(
+            self,
+            Action::Pause | Action::Restart | Action::Undo | Action::Redo | Action::Quit
+        )
+    }
+}
+
+// キー割り当て表
+// 1つの操作に任意の数のキーを割り当てられる
+// 設定ファイルでは`hold = "c"`や`hold = ["space", "c"]`のように書き、省略した操作はデフォルトの割り当てを使う
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bindings(BTreeMap<Action, Vec<KeyName>>);
+
+impl Default for Bindings {
+    fn default() -> Bindings {
+        use KeyName::*;
+        Bindings(BTreeMap::from([
+            (Action::MoveLeft, vec![Left]),
+            (Action::MoveRight, vec![Right]),
+            (Action::SoftDrop, vec![Down]),
+            (Action::HardDrop, vec![Up]),
+            (Action::RotateCW, vec![Char('x')]),
+            (Action::RotateCCW, vec![Char('z')]),
+            (Action::Rotate180, vec![Char('a')]),
+            (Action::Hold, vec![Space, Char('c')]),
+            (Action::Pause, vec![Char('p'), Esc]),
+            (Action::Restart, vec![F(4)]),
+            (Action::Undo, vec![Char('u')]),
+            (Action::Redo, vec![Char('r')]),
+            (Action::Quit, vec![Char('q')]),
+        ]))
+    }
+}
+
+impl Bindings {
+    // 操作に割り当てたキー
+    pub fn keys(&self, action: Action) -> &[KeyName] {
+        self.0.get(&action).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    // キーコードに割り当てた操作
+    pub fn action(&self, code: KeyCode) -> Option<Action> {
+        self.0
+            .iter()
+            .find(|(_, keys)| keys.iter().any(|key| key.key_code() == Some(code)))
+            .map(|(action, _)| *action)
+    }
+
+    // 操作に割り当てたキーのどれかを押しているか
+    pub fn pressed(&self, action: Action, input: &ButtonInput<KeyCode>) -> bool {
+        self.keys(action)
+            .iter()
+            .filter_map(|key| key.key_code())
+            .any(|code| input.pressed(code))
+    }
+
+    // 操作に割り当てたキーのどれかを押したか
+    pub fn just_pressed(&self, action: Action, input: &ButtonInput<KeyCode>) -> bool {
+        self.keys(action)
+            .iter()
+            .filter_map(|key| key.key_code())
+            .any(|code| input.just_pressed(code))
+    }
+
+    // 操作にキーを追加する
+    // 他の操作に割り当てていたキーはそちらから外す
+    pub fn bind(&mut self, action: Action, key: KeyName) {
+        for keys in self.0.values_mut() {
+            keys.retain(|k| *k != key);
+        }
+        self.0.entry(action).or_default().push(key);
+    }
+
+    // 操作に割り当てたキーをすべて外す
+    pub fn clear(&mut self, action: Action) {
+        self.0.insert(action, vec![]);
+    }
+
+    // 画面表示用の割り当てたキー(`space/c`など)
+    pub fn label(&self, action: Action) -> String {
+        self.keys(action)
+            .iter()
+            .map(KeyName::to_string)
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+impl Serialize for Bindings {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Bindings {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Bindings, D::Error> {
+        let table = BTreeMap::<Action, KeyList>::deserialize(deserializer)?;
+        let mut bindings = Bindings::default();
+        for (action, KeyList(keys)) in table {
+            bindings.0.insert(action, keys);
+        }
+        Ok(bindings)
+    }
+}
+
+// 設定ファイルに書いた1つの操作のキー(1つなら文字列、複数なら配列)
+struct KeyList(Vec<KeyName>);
+
+impl<'de> Deserialize<'de> for KeyList {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<KeyList, D::Error> {
+        struct KeyListVisitor;
+
+        impl<'de> Visitor<'de> for KeyListVisitor {
+            type Value = KeyList;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a key name or an array of key names")
+            }
+
+            fn visit_str<E: de::Error>(self, name: &str) -> Result<KeyList, E> {
+                KeyName::try_from(name.to_owned())
+                    .map(|key| KeyList(vec![key]))
+                    .map_err(E::custom)
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<KeyList, A::Error> {
+                let mut keys = vec![];
+                while let Some(key) = seq.next_element()? {
+                    keys.push(key);
+                }
+                Ok(KeyList(keys))
+            }
+        }
+
+        deserializer.deserialize_any(KeyListVisitor)
+    }
+}
+
+// 操作の感触
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Handling {
+    pub das: u64,                // 横移動のリピートが始まるまでの時間(ミリ秒)
+    pub arr: u64,                // 横移動のリピート間隔(ミリ秒、0なら壁まで移動)
+    pub sdf: u64,                // ソフトドロップの速さ(自然落下の何倍か)
+    pub lock_delay: Option<u64>, // 接地してから固定するまでの時間(ミリ秒、省略時は次の自然落下で固定)
+}
+
+impl Default for Handling {
+    fn default() -> Handling {
+        Handling {
+            das: 167,
+            arr: 33,
+            sdf: 20,
+            lock_delay: None,
+        }
+    }
+}
+
+// 表示
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Visual {
+    pub ghost: bool,             // ゴーストを表示するか
+    pub ghost_style: GhostStyle, // ゴーストの描き方
+    pub theme: Theme,            // 配色テーマ
+    pub color: ColorMode,        // 端末で使う色数(CLI版のみ)
+}
+
+impl Default for Visual {
+    fn default() -> Visual {
+        Visual {
+            ghost: true,
+            ghost_style: GhostStyle::Outline,
+            theme: Theme::Default,
+            color: ColorMode::Auto,
+        }
+    }
+}
+
+// モードごとのルール
+// GUI版は通常プレイのみなので`normal`を使い、他はCLI版と設定ファイルを共有するために読み込む
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModeRules {
+    pub normal: Ruleset, // 通常プレイと練習
+    pub versus: Ruleset, // 対戦(AIとの対戦を含む)
+    pub auto: Ruleset,   // オートプレイ
+    pub puzzle: Ruleset, // パズル
+}
+
+impl ModeRules {
+    // 設定ファイルでのモードの名前とルール
+    fn modes(&self) -> [(&'static str, &Ruleset); 4] {
+        [
+            ("normal", &self.normal),
+            ("versus", &self.versus),
+            ("auto", &self.auto),
+            ("puzzle", &self.puzzle),
+        ]
+    }
+}
+
+// ゴーストの描き方
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GhostStyle {
+    #[default]
+    Outline, // 白(CLI版では枠だけの`[]`)
+    Dim, // 操作中のブロックの色を薄くした色
+}
+
+// 配色テーマ
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    Default,
+    Pastel,
+    Mono,
+}
+
+// 端末で使う色数
+// GUIでは使わないが、CLI版と同じ設定ファイルを読めるように受け付ける
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMode {
+    #[default]
+    Auto,
+    Truecolor,
+    #[serde(rename = "256")]
+    Ansi256,
+    #[serde(rename = "16")]
+    Ansi16,
+    None,
+}
+
+// 設定ファイルで使うキーの名前
+// 1文字のキーはその文字、それ以外は`left`や`space`、`f1`などの名前で書く
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum KeyName {
+    Char(char),
+    Left,
+    Right,
+    Up,
+    Down,
+    Space,
+    Enter,
+    Tab,
+    Backspace,
+    Delete,
+    Esc,
+    F(u8), // ファンクションキー(1～12)
+}
+
+// GUIでキー割り当ての変更画面を開くキー
+// 設定ファイルを共有するため、どちらのフロントエンドでも操作には割り当てられない
+pub const RESERVED_KEY: KeyName = KeyName::F(1);
+
+// 端末版のパズルの画面で使うキー(最初からやり直す、次/前のパズル)
+// 設定ファイルを共有するため、パズルで受け付ける操作(ブロックの操作、1つ戻す、終了)には割り当てられない
+const PUZZLE_KEYS: [KeyName; 3] = [KeyName::Char('r'), KeyName::Char('n'), KeyName::Char('p')];
+
+// 端末版のリプレイの画面で使うキー(一時停止、速度の変更、1ブロック進める、移動)
+// 設定ファイルを共有するため、リプレイで受け付ける操作(終了)には割り当てられない
+const REPLAY_KEYS: [KeyName; 5] = [
+    KeyName::Space,
+    KeyName::Char('+'),
+    KeyName::Char('-'),
+    KeyName::Char('n'),
+    KeyName::Char('g'),
+];
+
+// 名前で書くキー(ファンクションキー以外)
+const NAMED_KEYS: [(&str, KeyName); 10] = [
+    ("left", KeyName::Left),
+    ("right", KeyName::Right),
+    ("up", KeyName::Up),
+    ("down", KeyName::Down),
+    ("space", KeyName::Space),
+    ("enter", KeyName::Enter),
+    ("tab", KeyName::Tab),
+    ("backspace", KeyName::Backspace),
+    ("delete", KeyName::Delete),
+    ("esc", KeyName::Esc),
+];
+
+impl TryFrom<String> for KeyName {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<KeyName, String> {
+        if let Some((_, key)) = NAMED_KEYS.iter().find(|(n, _)| *n == name) {
+            return Ok(*key);
+        }
+        if let Some(n) = name
+            .strip_prefix('f')
+            .and_then(|n| n.parse().ok())
+            .filter(|n| (1..=12).contains(n))
+        {
+            return Ok(KeyName::F(n));
+        }
+        let mut chars = name.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) if c.is_ascii_graphic() => Ok(KeyName::Char(c)),
+            _ => {
+                let names = NAMED_KEYS.map(|(n, _)| n).join(", ");
+                Err(format!(
+                    "unknown key \"{name}\" (expected a single character, f1-f12 or one of {names})"
+                ))
+            }
+        }
+    }
+}
+
+impl From<KeyName> for String {
+    fn from(key: KeyName) -> String {
+        key.to_string()
+    }
+}
+
+impl fmt::Display for KeyName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeyName::Char(c) => write!(f, "{c}"),
+            KeyName::F(n) => write!(f, "f{n}"),
+            key => {
+                let (name, _) = NAMED_KEYS.iter().find(|(_, k)| k == key).unwrap();
+                write!(f, "{name}")
+            }
+        }
+    }
+}
+
+impl KeyName {
+    // キーの名前に対応するキーコード
+    // 大文字と小文字は区別しない
+    pub fn key_code(self) -> Option<KeyCode> {
+        let code = match self {
+            KeyName::Left => KeyCode::ArrowLeft,
+            KeyName::Right => KeyCode::ArrowRight,
+            KeyName::Up => KeyCode::ArrowUp,
+            KeyName::Down => KeyCode::ArrowDown,
+            KeyName::Space => KeyCode::Space,
+            KeyName::Enter => KeyCode::Enter,
+            KeyName::Tab => KeyCode::Tab,
+            KeyName::Backspace => KeyCode::Backspace,
+            KeyName::Delete => KeyCode::Delete,
+            KeyName::Esc => KeyCode::Escape,
+            KeyName::F(n) => match n {
+                1 => KeyCode::F1,
+                2 => KeyCode::F2,
+                3 => KeyCode::F3,
+                4 => KeyCode::F4,
+                5 => KeyCode::F5,
+                6 => KeyCode::F6,
+                7 => KeyCode::F7,
+                8 => KeyCode::F8,
+                9 => KeyCode::F9,
+                10 => KeyCode::F10,
+                11 => KeyCode::F11,
+                12 => KeyCode::F12,
+                _ => return None,
+            },
+            KeyName::Char(c) => match c.to_ascii_lowercase() {
+                'a' => KeyCode::KeyA,
+                'b' => KeyCode::KeyB,
+                'c' => KeyCode::KeyC,
+                'd' => KeyCode::KeyD,
+                'e' => KeyCode::KeyE,
+                'f' => KeyCode::KeyF,
+                'g' => KeyCode::KeyG,
+                'h' => KeyCode::KeyH,
+                'i' => KeyCode::KeyI,
+                'j' => KeyCode::KeyJ,
+                'k' => KeyCode::KeyK,
+                'l' => KeyCode::KeyL,
+                'm' => KeyCode::KeyM,
+                'n' => KeyCode::KeyN,
+                'o' => KeyCode::KeyO,
+                'p' => KeyCode::KeyP,
+                'q' => KeyCode::KeyQ,
+                'r' => KeyCode::KeyR,
+                's' => KeyCode::KeyS,
+                't' => KeyCode::KeyT,
+                'u' => KeyCode::KeyU,
+                'v' => KeyCode::KeyV,
+                'w' => KeyCode::KeyW,
+                'x' => KeyCode::KeyX,
+                'y' => KeyCode::KeyY,
+                'z' => KeyCode::KeyZ,
+                '0' => KeyCode::Digit0,
+                '1' => KeyCode::Digit1,
+                '2' => KeyCode::Digit2,
+                '3' => KeyCode::Digit3,
+                '4' => KeyCode::Digit4,
+                '5' => KeyCode::Digit5,
+                '6' => KeyCode::Digit6,
+                '7' => KeyCode::Digit7,
+                '8' => KeyCode::Digit8,
+                '9' => KeyCode::Digit9,
+                ',' => KeyCode::Comma,
+                '.' => KeyCode::Period,
+                '/' => KeyCode::Slash,
+                ';' => KeyCode::Semicolon,
+                '\'' => KeyCode::Quote,
+                '[' => KeyCode::BracketLeft,
+                ']' => KeyCode::BracketRight,
+                '-' => KeyCode::Minus,
+                '=' => KeyCode::Equal,
+                '`' => KeyCode::Backquote,
+                '\\' => KeyCode::Backslash,
+                _ => return None,
+            },
+        };
+        Some(code)
+    }
+
+    // キーコードに対応するキーの名前
+    // 文字のキーは小文字の名前を返す
+    pub fn from_key_code(code: KeyCode) -> Option<KeyName> {
+        let named = NAMED_KEYS.iter().map(|(_, key)| *key);
+        let function = (1..=12).map(KeyName::F);
+        let chars = ('!'..='~')
+            .filter(|c| !c.is_ascii_uppercase())
+            .map(KeyName::Char);
+        named
+            .chain(function)
+            .chain(chars)
+            .find(|key| key.key_code() == Some(code))
+    }
+}
+
+impl Config {
+    // 設定値の範囲や重複を検証する
+    pub fn validate(&self) -> Result<(), String> {
+        for (i, action) in ACTIONS.iter().enumerate() {
+            for key in self.keys.keys(*action) {
+                // GUIではシフトを押して入力する記号などは使えない
+                if *key == RESERVED_KEY {
+                    return Err(format!(
+                        "keys.{}: \"{key}\" is reserved for the key bindings screen of the GUI",
+                        action.name()
+                    ));
+                }
+                if PUZZLE_KEYS.contains(key)
+                    && (action.is_operation() || matches!(action, Action::Undo | Action::Quit))
+                {
+                    return Err(format!(
+                        "keys.{}: \"{key}\" is used by the puzzle screen",
+                        action.name()
+                    ));
+                }
+                if REPLAY_KEYS.contains(key) && *action == Action::Quit {
+                    return Err(format!("keys.quit: \"{key}\" is used by the replay screen"));
+                }
+                let Some(code) = key.key_code() else {
+                    return Err(format!(
+                        "keys.{}: \"{key}\" is not available in the GUI",
+                        action.name()
+                    ));
+                };
+                // 同じキーを複数の操作に割り当てることはできない
+                if let Some(other) = ACTIONS[..i].iter().find(|a| {
+                    self.keys
+                        .keys(**a)
+                        .iter()
+                        .any(|k| k.key_code() == Some(code))
+                }) {
+                    return Err(format!(
+                        "key \"{key}\" is bound to both keys.{} and keys.{}",
+                        other.name(),
+                        action.name()
+                    ));
+                }
+            }
+        }
+        if self.keys.keys(Action::Quit).is_empty() {
+            return Err("keys.quit must have at least one key".to_owned());
+        }
+        let Handling {
+            das,
+            arr,
+            sdf,
+            lock_delay,
+        } = self.handling;
+        check_range("handling.das", das, 0, 1000)?;
+        check_range("handling.arr", arr, 0, 1000)?;
+        check_range("handling.sdf", sdf, 1, 100)?;
+        if let Some(lock_delay) = lock_delay {
+            check_range("handling.lock_delay", lock_delay, 0, 5000)?;
+        }
+        for (mode, rules) in self.rules.modes() {
+            check_range(
+                &format!("rules.{mode}.next"),
+                rules.next as u64,
+                0,
+                NEXT_MAX as u64,
+            )?;
+            check_range(&format!("rules.{mode}.are"), rules.are, 0, 1000)?;
+            check_range(
+                &format!("rules.{mode}.line_clear_delay"),
+                rules.line_clear_delay,
+                0,
+                1000,
+            )?;
+            check_range(
+                &format!("rules.{mode}.invisible_delay"),
+                rules.invisible_delay,
+                0,
+                10000,
+            )?;
+        }
+        // CLI版と同じく、対戦とパズルは通常の大きさのフィールドでのみ遊べる
+        for (mode, rules) in [
+            ("versus", &self.rules.versus),
+            ("puzzle", &self.rules.puzzle),
+        ] {
+            if rules.big {
+                return Err(format!("rules.{mode}.big is not supported"));
+            }
+        }
+        Ok(())
+    }
+}
+
+// 設定値が範囲内か検証する
+fn check_range(name: &str, value: u64, min: u64, max: u64) -> Result<(), String> {
+    if value < min || value > max {
+        return Err(format!(
+            "{name} must be between {min} and {max} (got {value})"
+        ));
+    }
+    Ok(())
+}
+
+// 設定ファイルを解析する
+pub fn parse(s: &str) -> Result<Config, String> {
+    let config: Config = toml::from_str(s).map_err(|e| e.to_string().trim_end().to_owned())?;
+    config.validate()?;
+    Ok(config)
+}
+
+// 設定ファイルの保存先ディレクトリ
+// `$XDG_CONFIG_HOME/tetrust`(未設定の場合は`~/.config/tetrust`)
+pub fn config_dir() -> PathBuf {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        .unwrap_or_default()
+        .join("tetrust")
+}
+
+// デフォルトの設定ファイルの保存先
+pub fn default_path() -> PathBuf {
+    config_dir().join("config.toml")
+}
+
+// 設定ファイルを読み込む
+// `path`を省略した場合はデフォルトの設定ファイルを読み込む(ファイルがなければデフォルトの設定を使う)
+pub fn load(path: Option<&Path>) -> Result<Config, String> {
+    let default_path = default_path();
+    let path_or_default = path.unwrap_or(&default_path);
+    let text = match fs::read_to_string(path_or_default) {
+        Ok(text) => text,
+        Err(e) if path.is_none() && e.kind() == io::ErrorKind::NotFound => {
+            return Ok(Config::default())
+        }
+        Err(e) => return Err(format!("failed to read {}: {e}", path_or_default.display())),
+    };
+    parse(&text).map_err(|e| format!("invalid config {}: {e}", path_or_default.display()))
+}
+
+// 設定ファイルに書き出す
+// ファイルにあったコメントは残らない
+pub fn save(path: &Path, config: &Config) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let text =
+        toml::to_string(config).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    fs::write(path, text)
+}
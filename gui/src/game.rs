@@ -2,14 +2,21 @@ use std::time::Duration;
 
 use bevy::{color::palettes::css::*, prelude::*};
 
-use crate::{despawn_screen, tetrust::*, GameState};
+use crate::{
+    config::{Action, Config, GhostStyle, Theme},
+    despawn_screen,
+    tetrust::*,
+    GameState,
+};
 
 pub struct GamePlugin;
 
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(Game::new())
+        app.insert_resource(Stats::new())
+            .init_resource::<AutoRepeat>()
             .insert_resource(Time::<Fixed>::from_duration(Duration::from_millis(1000)))
+            .add_systems(Startup, new_game)
             .add_systems(OnEnter(GameState::Game), setup)
             .add_systems(
                 OnExit(GameState::Game),
@@ -18,14 +25,37 @@ impl Plugin for GamePlugin {
             .add_systems(FixedUpdate, drop.run_if(in_state(GameState::Game)))
             .add_systems(
                 Update,
-                (draw, key_input, score_update).run_if(in_state(GameState::Game)),
-            );
+                (
+                    draw,
+                    key_input,
+                    entry.after(key_input),
+                    invisible.after(entry),
+                    stats_update.after(entry),
+                    score_update,
+                )
+                    .run_if(in_state(GameState::Game)),
+            )
+            .add_systems(Update, restart.run_if(not(in_state(GameState::KeyConfig))));
     }
 }
 
-fn setup(mut commands: Commands) {
-    commands.spawn(Camera2d);
+// 設定したルールで最初のゲームを始める
+fn new_game(mut commands: Commands, mut time: ResMut<Time<Fixed>>, config: Res<Config>) {
+    let game = Game::with_rules(config.rules.normal);
+    time.set_timestep(Duration::from_millis(game.rules.gravity.msec(0)));
+    commands.insert_resource(game);
+}
 
+fn setup(mut commands: Commands, config: Res<Config>) {
+    // ルールで使わないホールドやネクストの見出しは隠す
+    let visibility = |shown: bool| {
+        if shown {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        }
+    };
+    let rules = config.rules.normal;
     commands
         .spawn((
             GameScreen,
@@ -50,6 +80,20 @@ fn setup(mut commands: Commands) {
                     ..default()
                 },
             ));
+            parent.spawn((
+                StatsText,
+                Node {
+                    left: Val::Percent(2.),
+                    top: Val::Percent(10.),
+                    position_type: PositionType::Absolute,
+                    ..default()
+                },
+                Text::new(""),
+                TextFont {
+                    font_size: 18.,
+                    ..default()
+                },
+            ));
             parent.spawn((
                 Node {
                     right: Val::Percent(27.),
@@ -58,6 +102,7 @@ fn setup(mut commands: Commands) {
                     ..default()
                 },
                 Text::new("Hold"),
+                visibility(rules.hold),
             ));
             parent.spawn((
                 Node {
@@ -67,6 +112,7 @@ fn setup(mut commands: Commands) {
                     ..default()
                 },
                 Text::new("Next"),
+                visibility(rules.next > 0),
             ));
         });
 }
@@ -77,6 +123,9 @@ struct GameScreen;
 #[derive(Component)]
 struct Score;
 
+#[derive(Component)]
+struct StatsText;
+
 #[derive(Component)]
 struct Block;
 
@@ -105,12 +154,13 @@ impl BlockBundle {
 fn spawn_block<const Y: usize, const X: usize>(
     commands: &mut Commands,
     field: [[BlockColor; X]; Y],
+    theme: Theme,
     x: f32,
     y: f32,
 ) {
     for (y_i, y_field) in field.iter().enumerate() {
         for (x_i, block) in y_field.iter().enumerate() {
-            if let Some(color) = block_color(*block) {
+            if let Some(color) = block_color(*block, theme) {
                 let x = (x_i as f32 * 25.) + x;
                 let y = (y_i as f32 * 25.) + y;
 
@@ -120,7 +170,12 @@ fn spawn_block<const Y: usize, const X: usize>(
     }
 }
 
-fn draw(mut commands: Commands, game: Res<Game>, query: Query<Entity, With<Sprite>>) {
+fn draw(
+    mut commands: Commands,
+    game: Res<Game>,
+    config: Res<Config>,
+    query: Query<Entity, With<Sprite>>,
+) {
     query.iter().for_each(|e| commands.entity(e).despawn());
 
     let Game {
@@ -129,40 +184,57 @@ fn draw(mut commands: Commands, game: Res<Game>, query: Query<Entity, With<Sprit
         block,
         hold,
         next,
+        rules,
+        entry,
         ..
     } = game.as_ref();
 
-    if let Some(block) = hold {
-        spawn_block(&mut commands, *block, 275., 250.);
+    let theme = config.visual.theme;
+
+    if let Some(block) = hold.filter(|_| rules.hold) {
+        spawn_block(&mut commands, block, theme, 275., 250.);
     }
 
-    for (i, next) in next.iter().take(NEXT_LENGTH).rev().enumerate() {
-        spawn_block(&mut commands, *next, 275., (75. * i as f32) - 50.);
+    // 出現待ちの間は出現するブロックをフィールドに描画せず、ネクストの先頭に描画する
+    let entering = entry.is_some();
+
+    // ルールで設定した数だけネクストを上から並べる
+    let queue = entering.then_some(block).into_iter().chain(next);
+    for (i, next) in queue.take(rules.next).enumerate() {
+        spawn_block(&mut commands, *next, theme, 275., 100. - (75. * i as f32));
     }
 
     let mut field_buf = *field;
     // 描画用フィールドにゴーストブロックを書き込む
-    let ghost_pos = ghost_pos(field, pos, block);
-    for y in 0..4 {
-        for x in 0..4 {
-            if block[y][x] != block_kind::NONE {
-                field_buf[y + ghost_pos.y][x + ghost_pos.x] = block_kind::GHOST;
+    if config.visual.ghost && rules.ghost && !entering {
+        let ghost_pos = ghost_pos(field, pos, block);
+        for y in 0..4 {
+            for x in 0..4 {
+                if block[y][x] != block_kind::NONE {
+                    field_buf[y + ghost_pos.y][x + ghost_pos.x] = match config.visual.ghost_style {
+                        GhostStyle::Outline => block_kind::GHOST,
+                        GhostStyle::Dim => dim_color(block[y][x]),
+                    };
+                }
             }
         }
     }
     // 描画用フィールドにブロックの情報を書き込む
     for y in 0..4 {
         for x in 0..4 {
-            if block[y][x] != block_kind::NONE {
+            if block[y][x] != block_kind::NONE && !entering {
                 field_buf[y + pos.y][x + pos.x] = block[y][x];
             }
         }
     }
 
+    // インビジブルとでかブロックの表示に変換する
+    let field_buf = display_field(&game, &field_buf);
+
     for (y_i, y) in field_buf.iter().enumerate() {
         // println!("{:?}", y);
         for (x_i, block) in y.iter().enumerate() {
-            if let Some(color) = block_color(*block) {
+            if let Some(color) = block_color(*block, theme) {
                 let x = (x_i * 25) as f32 - 100.;
                 let y = (y_i * 25) as f32 - 200.;
 
@@ -172,42 +244,156 @@ fn draw(mut commands: Commands, game: Res<Game>, query: Query<Entity, With<Sprit
     }
 }
 
+// キーを押し続けたときのリピートの状態
+#[derive(Default)]
+struct Repeat {
+    held: Option<Duration>, // 押し続けている時間(押していなければ`None`)
+}
+
+impl Repeat {
+    fn press(&mut self) {
+        self.held = Some(Duration::ZERO);
+    }
+
+    fn release(&mut self) {
+        self.held = None;
+    }
+
+    // 押し続けている時間を進め、その間にリピートした回数を返す
+    // 押してから`delay`経過時に1回、以降は`interval`ごとに1回(`interval`が0なら上限なし)
+    fn tick(&mut self, delta: Duration, delay: Duration, interval: Duration) -> usize {
+        let Some(held) = &mut self.held else {
+            return 0;
+        };
+        let count = |held: Duration| match held.checked_sub(delay) {
+            None => 0,
+            Some(_) if interval.is_zero() => usize::MAX,
+            Some(t) => 1 + (t.as_nanos() / interval.as_nanos()) as usize,
+        };
+        let before = count(*held);
+        *held += delta;
+        match count(*held) {
+            usize::MAX => usize::MAX,
+            after => after - before,
+        }
+    }
+}
+
+// 横移動(DAS/ARR)とソフトドロップのリピート
+#[derive(Resource, Default)]
+struct AutoRepeat {
+    left: bool, // 横移動の向き
+    shift: Repeat,
+    soft_drop: Repeat,
+}
+
+// 横に1マス移動する
+// 移動できた場合は`true`を返す(出現待ちの間は移動しない)
+fn shift(game: &mut Game, left: bool) -> bool {
+    if game.entry.is_some() {
+        return false;
+    }
+    let x = game.pos.x;
+    let new_pos = Position {
+        x: if left {
+            x.checked_sub(1).unwrap_or(x)
+        } else {
+            x + 1
+        },
+        y: game.pos.y,
+    };
+    move_block(game, new_pos);
+    game.pos.x != x
+}
+
+// 1マス落下する(出現待ちの間は落下しない)
+fn soft_drop(game: &mut Game) {
+    if game.entry.is_some() {
+        return;
+    }
+    let new_pos = Position {
+        x: game.pos.x,
+        y: game.pos.y + 1,
+    };
+    move_block(game, new_pos);
+}
+
 fn key_input(
     mut game: ResMut<Game>,
+    mut stats: ResMut<Stats>,
+    mut repeat: ResMut<AutoRepeat>,
+    config: Res<Config>,
+    time: Res<Time>,
     input: Res<ButtonInput<KeyCode>>,
     mut state: ResMut<NextState<GameState>>,
 ) {
-    if input.just_pressed(KeyCode::ArrowLeft) {
-        let new_pos = Position {
-            x: game.pos.x.checked_sub(1).unwrap_or(game.pos.x),
-            y: game.pos.y,
-        };
-        move_block(&mut game, new_pos);
+    let keys = &config.keys;
+    let handling = &config.handling;
+    // 操作に使うキーの入力数を記録
+    for _ in input
+        .get_just_pressed()
+        .filter(|code| keys.action(**code).is_some_and(Action::is_operation))
+    {
+        stats.input();
     }
-    if input.just_pressed(KeyCode::ArrowDown) {
-        let new_pos = Position {
-            x: game.pos.x,
-            y: game.pos.y + 1,
-        };
-        move_block(&mut game, new_pos);
+    // 横移動
+    // 後から押した向きを優先し、離したときにもう一方を押していればそちらにリピートする
+    for (action, left) in [(Action::MoveLeft, true), (Action::MoveRight, false)] {
+        if keys.just_pressed(action, &input) {
+            repeat.left = left;
+            repeat.shift.press();
+            shift(&mut game, left);
+        }
     }
-    if input.just_pressed(KeyCode::ArrowRight) {
-        let new_pos = Position {
-            x: game.pos.x + 1,
-            y: game.pos.y,
-        };
-        move_block(&mut game, new_pos);
+    let (held, other) = if repeat.left {
+        (Action::MoveLeft, Action::MoveRight)
+    } else {
+        (Action::MoveRight, Action::MoveLeft)
+    };
+    if !keys.pressed(held, &input) {
+        if keys.pressed(other, &input) {
+            repeat.left = !repeat.left;
+            repeat.shift.press();
+        } else {
+            repeat.shift.release();
+        }
+    }
+    let das = Duration::from_millis(handling.das);
+    let arr = Duration::from_millis(handling.arr);
+    let left = repeat.left;
+    for _ in 0..repeat.shift.tick(time.delta(), das, arr) {
+        if !shift(&mut game, left) {
+            break;
+        }
     }
-    if input.just_pressed(KeyCode::KeyZ) {
+    // ソフトドロップ(自然落下の`sdf`倍の速さでリピート)
+    if keys.just_pressed(Action::SoftDrop, &input) {
+        repeat.soft_drop.press();
+        soft_drop(&mut game);
+    } else if !keys.pressed(Action::SoftDrop, &input) {
+        repeat.soft_drop.release();
+    }
+    let interval = Duration::from_millis(game.rules.gravity.msec(game.line)) / handling.sdf as u32;
+    let drops = repeat
+        .soft_drop
+        .tick(time.delta(), Duration::ZERO, interval)
+        .min(FIELD_HEIGHT);
+    for _ in 0..drops {
+        soft_drop(&mut game);
+    }
+    if keys.just_pressed(Action::RotateCCW, &input) {
         // 左回転
         rotate_left(&mut game);
     }
-    if input.just_pressed(KeyCode::KeyX) {
+    if keys.just_pressed(Action::RotateCW, &input) {
         // 右回転
         rotate_right(&mut game);
     }
-    // up
-    if input.just_pressed(KeyCode::ArrowUp) {
+    if keys.just_pressed(Action::Rotate180, &input) {
+        // 180度回転
+        rotate_180(&mut game);
+    }
+    if keys.just_pressed(Action::HardDrop, &input) && game.entry.is_none() && game.rules.hard_drop {
         // ハードドロップ
         hard_drop(&mut game);
         if landing(&mut game).is_err() {
@@ -215,40 +401,113 @@ fn key_input(
             state.set(GameState::GameOver);
         }
     }
-    if input.just_pressed(KeyCode::Space) {
+    if keys.just_pressed(Action::Hold, &input) {
         // ホールド
         hold(&mut game);
     }
 }
 
+// 出現待ち(ARE)の時間が経過したら次のブロックを操作できるようにする
+fn entry(mut game: ResMut<Game>, time: Res<Time>, mut waited: Local<Duration>) {
+    let Some(entry) = game.entry else {
+        *waited = Duration::ZERO;
+        return;
+    };
+    *waited += time.delta();
+    if *waited >= Duration::from_millis(entry.delay) {
+        *waited = Duration::ZERO;
+        enter(&mut game);
+    }
+}
+
+// インビジブルでは固定したブロックごとに`invisible_delay`経過したら見えなくする
+// 一時停止中やゲームオーバー後は経過時間を数えない
+fn invisible(
+    mut game: ResMut<Game>,
+    time: Res<Time>,
+    mut waited: Local<Vec<Duration>>,
+    mut scheduled: Local<usize>,
+) {
+    // やり直した場合は待っているブロックを破棄する
+    if game.locks < *scheduled {
+        waited.clear();
+        *scheduled = 0;
+    }
+    // 新たに固定したブロックの待ち時間を数え始める
+    if game.rules.invisible && game.rules.invisible_delay > 0 {
+        waited.extend((*scheduled..game.locks).map(|_| Duration::ZERO));
+    }
+    *scheduled = game.locks;
+    let delay = Duration::from_millis(game.rules.invisible_delay);
+    for waited in waited.iter_mut() {
+        *waited += time.delta();
+    }
+    while waited.first().is_some_and(|&waited| waited >= delay) {
+        waited.remove(0);
+        fade(&mut game);
+    }
+}
+
+// やり直しキーで新しいゲームを始める
+// 一時停止中やゲームオーバー後もやり直せる(中断したゲームはハイスコアに記録しない)
+fn restart(
+    mut game: ResMut<Game>,
+    mut stats: ResMut<Stats>,
+    mut repeat: ResMut<AutoRepeat>,
+    mut time: ResMut<Time<Fixed>>,
+    config: Res<Config>,
+    input: Res<ButtonInput<KeyCode>>,
+    mut state: ResMut<NextState<GameState>>,
+) {
+    if !config.keys.just_pressed(Action::Restart, &input) {
+        return;
+    }
+    *game = Game::with_rules(config.rules.normal);
+    *stats = Stats::new();
+    *repeat = AutoRepeat::default();
+    time.set_timestep(Duration::from_millis(game.rules.gravity.msec(0)));
+    state.set(GameState::Game);
+}
+
 // 自然落下
+// 固定猶予(lock delay)が設定されている場合は、接地を検出してから猶予の間は固定しない
 fn drop(
     mut game: ResMut<Game>,
     mut time: ResMut<Time<Fixed>>,
+    config: Res<Config>,
+    mut grounded: Local<bool>,
     mut state: ResMut<NextState<GameState>>,
 ) {
-    // nミリ秒間スリーブする
-    let sleep_msec = match 1000u64.saturating_sub((game.line as u64 / 10) * 100) {
-        0 => 100,
-        msec => msec,
-    };
-    // drop関数を呼び出す間隔を変更
-    time.set_timestep(Duration::from_millis(sleep_msec));
-
+    if game.entry.is_some() {
+        // 出現待ちの間は落下しない
+        return;
+    }
+    let lock_delay = config.handling.lock_delay;
     let new_pos = Position {
         x: game.pos.x,
         y: game.pos.y + 1,
     };
     if !is_collision(&game.field, &new_pos, &game.block) {
         // posの座標を更新
-        game.pos = new_pos;
+        move_block(&mut game, new_pos);
+        *grounded = false;
+    } else if lock_delay.is_some() && !*grounded {
+        // 固定猶予の間は固定を待つ
+        *grounded = true;
     } else {
+        *grounded = false;
         // ブロック落下後の処理
         if landing(&mut game).is_err() {
             // ブロックを生成できないならゲームオーバー
             state.set(GameState::GameOver);
         }
     }
+    // drop関数を呼び出す間隔を変更
+    let msec = match lock_delay {
+        Some(lock_delay) if *grounded => lock_delay.max(1),
+        _ => game.rules.gravity.msec(game.line),
+    };
+    time.set_timestep(Duration::from_millis(msec));
 }
 
 // スコアの更新
@@ -256,18 +515,46 @@ fn score_update(mut query: Query<&mut Text, With<Score>>, game: Res<Game>) {
     query.single_mut().0 = format!("score: {}", game.score);
 }
 
-const fn block_color(block_color: BlockColor) -> Option<Srgba> {
-    match block_color {
-        0 => None,
-        1 => Some(GRAY),
-        2 => Some(WHITE),
-        3 => Some(BLUE),
-        4 => Some(GREEN),
-        5 => Some(RED),
-        6 => Some(SKY_BLUE),
-        7 => Some(PINK),
-        8 => Some(ORANGE),
-        9 => Some(YELLOW),
+// 統計の更新
+fn stats_update(
+    mut query: Query<&mut Text, With<StatsText>>,
+    mut game: ResMut<Game>,
+    mut stats: ResMut<Stats>,
+) {
+    for event in game.take_events() {
+        stats.record(&event);
+    }
+    query.single_mut().0 = stats
+        .summary()
+        .iter()
+        .map(|(label, value)| format!("{label}: {value}\n"))
+        .collect();
+}
+
+// 配色テーマごとの表示ブロックの色
+fn block_color(block_color: BlockColor, theme: Theme) -> Option<Srgba> {
+    match (block_color, theme) {
+        (0, _) => None,
+        (1, _) => Some(GRAY),
+        (2, _) => Some(WHITE),
+        // 薄い色のゴーストはブロックの色を暗くする
+        (10..=16, _) => self::block_color(block_color - block_kind::DIM_I + block_kind::I, theme)
+            .map(|color| color.mix(&BLACK, 0.6)),
+        (3..=9, Theme::Mono) => Some(LIGHT_GRAY),
+        (3, Theme::Default) => Some(BLUE),
+        (4, Theme::Default) => Some(GREEN),
+        (5, Theme::Default) => Some(RED),
+        (6, Theme::Default) => Some(SKY_BLUE),
+        (7, Theme::Default) => Some(PINK),
+        (8, Theme::Default) => Some(ORANGE),
+        (9, Theme::Default) => Some(YELLOW),
+        (3, Theme::Pastel) => Some(LIGHT_SKY_BLUE),
+        (4, Theme::Pastel) => Some(PALE_GREEN),
+        (5, Theme::Pastel) => Some(LIGHT_CORAL),
+        (6, Theme::Pastel) => Some(PALE_TURQUOISE),
+        (7, Theme::Pastel) => Some(PLUM),
+        (8, Theme::Pastel) => Some(PEACHPUFF),
+        (9, Theme::Pastel) => Some(LIGHT_YELLOW),
         _ => panic!(),
     }
 }
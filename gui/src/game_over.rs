@@ -1,6 +1,12 @@
 use bevy::prelude::*;
 
-use crate::{despawn_screen, GameState};
+use crate::{
+    config::{Action, Config},
+    despawn_screen,
+    highscore::{self, Entry},
+    tetrust::{Game, Stats},
+    GameState,
+};
 
 pub struct GameOverPlugin;
 
@@ -17,7 +23,25 @@ impl Plugin for GameOverPlugin {
 #[derive(Component)]
 struct GameOverScreen;
 
-fn game_over_setup(mut commands: Commands) {
+fn game_over_setup(
+    mut commands: Commands,
+    game: Res<Game>,
+    stats: Res<Stats>,
+    config: Res<Config>,
+) {
+    // ハイスコアの記録
+    let entry = Entry {
+        date: highscore::now(),
+        name: highscore::player_name(),
+        score: game.score,
+        time: stats.elapsed().as_secs(),
+        lines: game.line,
+        settings: highscore::settings("gui", &game.rules),
+    };
+    let path = highscore::default_path();
+    let ranking = highscore::record(&path, &highscore::mode_name("normal", &game.rules), entry)
+        .map_err(|e| format!("failed to record high score to {}: {e}", path.display()));
+
     commands
         .spawn((
             GameOverScreen,
@@ -32,6 +56,55 @@ fn game_over_setup(mut commands: Commands) {
         ))
         .with_children(|parent| {
             parent.spawn((Text::new("Game Over"), TextFont::default()));
-            parent.spawn((Text::new("Pressed q key to exit"), TextFont::default()));
+            // 統計の表示
+            for (label, value) in stats.summary() {
+                parent.spawn((
+                    Text::new(format!("{label}: {value}")),
+                    TextFont {
+                        font_size: 18.,
+                        ..default()
+                    },
+                ));
+            }
+            // ハイスコアの表示
+            match &ranking {
+                Ok(ranking) => {
+                    let mut title = format!("High Scores ({})", ranking.mode);
+                    if ranking.is_personal_best() {
+                        title.push_str("  New Personal Best!");
+                    }
+                    parent.spawn((Text::new(title), TextFont::default()));
+                    for (i, entry) in ranking.entries.iter().enumerate() {
+                        let mark = if ranking.rank == Some(i) { "<" } else { "" };
+                        parent.spawn((
+                            Text::new(format!(
+                                "{:>2}. {:>6}  {:>3} lines  {:>2}:{:02}  {}  {}  {mark}",
+                                i + 1,
+                                entry.score,
+                                entry.lines,
+                                entry.time / 60,
+                                entry.time % 60,
+                                entry.date,
+                                entry.name,
+                            )),
+                            TextFont {
+                                font_size: 18.,
+                                ..default()
+                            },
+                        ));
+                    }
+                }
+                Err(e) => {
+                    parent.spawn((Text::new(e.as_str()), TextFont::default()));
+                }
+            }
+            parent.spawn((
+                Text::new(format!(
+                    "Pressed {} key to exit, {} key to restart",
+                    config.keys.label(Action::Quit),
+                    config.keys.label(Action::Restart)
+                )),
+                TextFont::default(),
+            ));
         });
 }
@@ -0,0 +1,187 @@
+use bevy::prelude::*;
+
+use crate::{
+    config::{self, Bindings, Config, KeyName, ACTIONS, RESERVED_KEY},
+    despawn_screen, ConfigPath, GameState,
+};
+
+pub struct KeyConfigPlugin;
+
+impl Plugin for KeyConfigPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::KeyConfig), key_config_setup)
+            .add_systems(
+                OnExit(GameState::KeyConfig),
+                despawn_screen::<KeyConfigScreen>,
+            )
+            .add_systems(Update, open_key_config.run_if(in_state(GameState::Game)))
+            .add_systems(
+                Update,
+                (key_input, draw.after(key_input)).run_if(in_state(GameState::KeyConfig)),
+            );
+    }
+}
+
+#[derive(Component)]
+struct KeyConfigScreen;
+
+#[derive(Component)]
+struct KeyList;
+
+// キー割り当ての変更画面の状態
+#[derive(Resource)]
+struct Rebind {
+    keys: Bindings,          // 編集中のキー割り当て
+    selected: usize,         // 選んでいる操作(`ACTIONS`の添字)
+    capturing: bool,         // 割り当てるキーの入力待ちか
+    message: Option<String>, // 直前の操作の結果
+}
+
+// ゲーム中にF1キー(`RESERVED_KEY`、操作には割り当てられない)でキー割り当ての変更画面を開く
+fn open_key_config(input: Res<ButtonInput<KeyCode>>, mut state: ResMut<NextState<GameState>>) {
+    if RESERVED_KEY
+        .key_code()
+        .is_some_and(|code| input.just_pressed(code))
+    {
+        state.set(GameState::KeyConfig);
+    }
+}
+
+fn key_config_setup(mut commands: Commands, config: Res<Config>) {
+    commands.insert_resource(Rebind {
+        keys: config.keys.clone(),
+        selected: 0,
+        capturing: false,
+        message: None,
+    });
+
+    commands
+        .spawn((
+            KeyConfigScreen,
+            Node {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((Text::new("Key Bindings"), TextFont::default()));
+            parent.spawn((
+                KeyList,
+                Text::default(),
+                TextFont {
+                    font_size: 20.,
+                    ..default()
+                },
+            ));
+        });
+}
+
+// キー割り当ての変更
+// 次のキーは割り当てに関係なく固定
+// - 上下キー: 操作を選ぶ
+// - Enterキー: 選んだ操作にキーを追加する(次に押したキーを割り当てる)
+// - Backspace/Deleteキー: 選んだ操作の割り当てをすべて外す
+// - Dキー: すべてデフォルトの割り当てに戻す
+// - Sキー: 設定ファイルに保存してゲームに戻る
+// - Escape/F1キー: 保存せずにゲームに戻る
+fn key_input(
+    mut rebind: ResMut<Rebind>,
+    mut config: ResMut<Config>,
+    path: Res<ConfigPath>,
+    input: Res<ButtonInput<KeyCode>>,
+    mut state: ResMut<NextState<GameState>>,
+) {
+    let Some(&code) = input.get_just_pressed().next() else {
+        return;
+    };
+    let action = ACTIONS[rebind.selected];
+    if rebind.capturing {
+        // 押したキーを割り当てる
+        rebind.capturing = false;
+        rebind.message = match KeyName::from_key_code(code) {
+            Some(RESERVED_KEY) => Some(format!("{RESERVED_KEY} is reserved")),
+            Some(key) => {
+                rebind.keys.bind(action, key);
+                None
+            }
+            None => Some(format!("{code:?} cannot be bound")),
+        };
+        return;
+    }
+    rebind.message = None;
+    match code {
+        KeyCode::ArrowUp => {
+            rebind.selected = rebind.selected.checked_sub(1).unwrap_or(ACTIONS.len() - 1);
+        }
+        KeyCode::ArrowDown => {
+            rebind.selected = (rebind.selected + 1) % ACTIONS.len();
+        }
+        KeyCode::Enter => {
+            rebind.capturing = true;
+        }
+        KeyCode::Backspace | KeyCode::Delete => {
+            rebind.keys.clear(action);
+        }
+        KeyCode::KeyD => {
+            rebind.keys = Bindings::default();
+        }
+        KeyCode::KeyS => {
+            let new_config = Config {
+                keys: rebind.keys.clone(),
+                ..config.clone()
+            };
+            let saved = new_config.validate().and_then(|()| {
+                config::save(&path.0, &new_config)
+                    .map_err(|e| format!("failed to save {}: {e}", path.0.display()))
+            });
+            match saved {
+                Ok(()) => {
+                    *config = new_config;
+                    state.set(GameState::Game);
+                }
+                Err(e) => rebind.message = Some(e),
+            }
+        }
+        KeyCode::Escape | KeyCode::F1 => {
+            state.set(GameState::Game);
+        }
+        _ => {}
+    }
+}
+
+// 操作と割り当てたキーの一覧を描画する
+fn draw(rebind: Res<Rebind>, mut query: Query<&mut Text, With<KeyList>>) {
+    if !rebind.is_changed() {
+        return;
+    }
+    let mut lines: Vec<String> = ACTIONS
+        .iter()
+        .enumerate()
+        .map(|(i, action)| {
+            let cursor = if i == rebind.selected { ">" } else { " " };
+            format!(
+                "{cursor} {:<12}{}",
+                action.name(),
+                rebind.keys.label(*action)
+            )
+        })
+        .collect();
+    lines.push(String::new());
+    if rebind.capturing {
+        lines.push(format!(
+            "press a key for {}",
+            ACTIONS[rebind.selected].name()
+        ));
+    } else {
+        lines.push("[up/down] select  [enter] add key  [backspace] clear".to_owned());
+        lines.push("[d] defaults  [s] save  [esc] cancel".to_owned());
+    }
+    if let Some(message) = &rebind.message {
+        lines.push(message.clone());
+    }
+    query.single_mut().0 = lines.join("\n");
+}
@@ -1,29 +1,79 @@
+mod config;
 mod game;
 mod game_over;
+mod highscore;
+mod key_config;
+mod pause;
 mod tetrust;
 
 use bevy::prelude::*;
+use std::path::PathBuf;
 
+use config::{Action, Config};
 use game::GamePlugin;
 use game_over::GameOverPlugin;
+use key_config::KeyConfigPlugin;
+use pause::PausePlugin;
 
 #[derive(Debug, Default, States, Clone, Hash, PartialEq, Eq)]
 enum GameState {
     GameOver,
     #[default]
     Game,
+    Paused,
+    KeyConfig,
 }
 
+// 設定ファイルの保存先(キー割り当ての変更画面で使う)
+#[derive(Resource)]
+struct ConfigPath(PathBuf);
+
 fn main() {
+    // 設定ファイルの読み込み
+    let path = config_arg();
+    let config = match config::load(path.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("error: {e}");
+            std::process::exit(2);
+        }
+    };
     App::new()
-        .add_plugins((DefaultPlugins, GamePlugin, GameOverPlugin))
+        .add_plugins((
+            DefaultPlugins,
+            GamePlugin,
+            GameOverPlugin,
+            PausePlugin,
+            KeyConfigPlugin,
+        ))
+        .insert_resource(config)
+        .insert_resource(ConfigPath(path.unwrap_or_else(config::default_path)))
         .init_state::<GameState>()
-        .add_systems(Update, pressed_q_exit)
+        .add_systems(Startup, setup_camera)
+        .add_systems(
+            Update,
+            pressed_quit_exit.run_if(not(in_state(GameState::KeyConfig))),
+        )
         .run();
 }
 
-fn pressed_q_exit(input: Res<ButtonInput<KeyCode>>) {
-    if input.just_pressed(KeyCode::KeyQ) {
+// コマンドライン引数`--config <FILE>`で指定した設定ファイル
+fn config_arg() -> Option<PathBuf> {
+    let mut args = std::env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            return args.next().map(PathBuf::from);
+        }
+    }
+    None
+}
+
+fn setup_camera(mut commands: Commands) {
+    commands.spawn(Camera2d);
+}
+
+fn pressed_quit_exit(input: Res<ButtonInput<KeyCode>>, config: Res<Config>) {
+    if config.keys.just_pressed(Action::Quit, &input) {
         std::process::exit(0)
     }
 }
@@ -0,0 +1,104 @@
+use bevy::prelude::*;
+
+use crate::{
+    config::{Action, Config},
+    despawn_screen,
+    tetrust::Stats,
+    GameState,
+};
+
+// 一時停止から再開するまでのカウントダウン(秒)
+const RESUME_COUNTDOWN: f32 = 3.;
+
+pub struct PausePlugin;
+
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Paused), pause_setup)
+            .add_systems(OnExit(GameState::Paused), despawn_screen::<PauseScreen>)
+            .add_systems(Update, pressed_pause.run_if(in_state(GameState::Game)))
+            .add_systems(Update, resume.run_if(in_state(GameState::Paused)));
+    }
+}
+
+#[derive(Component)]
+struct PauseScreen;
+
+#[derive(Component)]
+struct PauseText;
+
+// 再開までのカウントダウン(一時停止キーを押すまでは`None`)
+#[derive(Resource, Default)]
+struct Countdown(Option<Timer>);
+
+// ゲーム中に一時停止キーで一時停止する
+// 一時停止中は盤面を隠す
+fn pressed_pause(
+    input: Res<ButtonInput<KeyCode>>,
+    config: Res<Config>,
+    mut stats: ResMut<Stats>,
+    mut state: ResMut<NextState<GameState>>,
+) {
+    if config.keys.just_pressed(Action::Pause, &input) {
+        stats.pause();
+        state.set(GameState::Paused);
+    }
+}
+
+fn pause_setup(mut commands: Commands, config: Res<Config>) {
+    // 前回の一時停止やカウントダウン中のやり直しで残ったタイマーを破棄する
+    commands.insert_resource(Countdown::default());
+
+    commands
+        .spawn((
+            PauseScreen,
+            Node {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((PauseText, Text::new("Paused"), TextFont::default()));
+            parent.spawn((
+                Text::new(format!(
+                    "[{}] resume  [{}] restart  [{}] quit",
+                    config.keys.label(Action::Pause),
+                    config.keys.label(Action::Restart),
+                    config.keys.label(Action::Quit)
+                )),
+                TextFont {
+                    font_size: 18.,
+                    ..default()
+                },
+            ));
+        });
+}
+
+// 一時停止キーを押したらカウントダウンの後に再開する
+fn resume(
+    mut countdown: ResMut<Countdown>,
+    mut query: Query<&mut Text, With<PauseText>>,
+    mut stats: ResMut<Stats>,
+    config: Res<Config>,
+    time: Res<Time>,
+    input: Res<ButtonInput<KeyCode>>,
+    mut state: ResMut<NextState<GameState>>,
+) {
+    let Some(timer) = &mut countdown.0 else {
+        if config.keys.just_pressed(Action::Pause, &input) {
+            countdown.0 = Some(Timer::from_seconds(RESUME_COUNTDOWN, TimerMode::Once));
+        }
+        return;
+    };
+    if timer.tick(time.delta()).finished() {
+        stats.resume();
+        state.set(GameState::Game);
+        return;
+    }
+    let remaining = timer.remaining_secs().ceil();
+    query.single_mut().0 = format!("Resuming in {remaining}");
+}
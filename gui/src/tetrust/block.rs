@@ -4,6 +4,7 @@ use rand::{
     thread_rng,
     Rng,
 };
+use serde::{Deserialize, Serialize};
 use block_kind::{I, O, S, Z, J, L, T};
 
 pub type BlockColor = usize;
@@ -19,6 +20,7 @@ pub mod block_kind {
     pub const J:     super::BlockColor = 7;
     pub const L:     super::BlockColor = 8;
     pub const T:     super::BlockColor = 9;
+    pub const DIM_I: super::BlockColor = 10;  // 薄い色のゴースト(描画用、ここから7つがI～Tと同じ順)
 }
 
 // ブロックの種類
@@ -102,6 +104,14 @@ pub const BLOCKS: [BlockShape; BLOCK_KIND_MAX] = [
     ],
 ];
 
+// ブロックの生成方式
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Randomizer {
+    Bag7,  // 7種1巡
+    Nes,   // NES版と同じ方式(直前と同じブロックなら1回だけ引き直す)
+}
+
 // シャッフルされた7種のブロックを生成
 pub fn gen_block_7() -> [BlockShape; BLOCK_KIND_MAX] {
     let mut rng = thread_rng();
@@ -117,3 +127,30 @@ pub fn gen_block_7() -> [BlockShape; BLOCK_KIND_MAX] {
     que.shuffle(&mut rng);
     que.map(|block| BLOCKS[block as usize])
 }
+
+// NES版と同じ方式でブロックを1つ生成
+// 7種と「引き直し」の8通りから選び、引き直しか直前と同じブロックなら7種から1回だけ引き直す
+pub fn gen_block_nes(prev: Option<&BlockShape>) -> BlockShape {
+    let mut rng = thread_rng();
+    let roll = rng.gen_range(0..=7);
+    if roll < BLOCK_KIND_MAX && prev.map(spawn_shape) != Some(BLOCKS[roll]) {
+        return BLOCKS[roll];
+    }
+    BLOCKS[rng.gen::<BlockKind>() as usize]
+}
+
+// ブロックの色に対応する薄い色のゴーストを返す
+pub fn dim_color(color: BlockColor) -> BlockColor {
+    color - I + block_kind::DIM_I
+}
+
+// 回転したブロックの形状から出現時の向きの形状を返す
+pub fn spawn_shape(shape: &BlockShape) -> BlockShape {
+    let color = shape
+        .iter()
+        .flatten()
+        .copied()
+        .find(|&b| b != block_kind::NONE)
+        .unwrap_or(I);
+    BLOCKS[color - I]
+}
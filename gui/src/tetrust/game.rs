@@ -1,7 +1,9 @@
 use bevy::prelude::Resource;
+use serde::{Deserialize, Serialize};
 
 use super::block::{
-    block_kind, block_kind::WALL as W, gen_block_7, BlockColor, BlockKind, BlockShape, BLOCKS,
+    block_kind, block_kind::WALL as W, gen_block_7, gen_block_nes, spawn_shape, BlockColor,
+    BlockKind, BlockShape, Randomizer, BLOCKS,
 };
 use std::collections::VecDeque;
 
@@ -14,13 +16,258 @@ pub const SCORE_TABLE: [usize; 5] = [
     100, // 4段消し
 ];
 
+// 攻撃力表(対戦時に送るおじゃまライン数)
+pub const GARBAGE_TABLE: [usize; 5] = [
+    0, // 0段消し
+    0, // 1段消し
+    1, // 2段消し
+    2, // 3段消し
+    4, // 4段消し
+];
+
 // フィールドサイズ
 pub const FIELD_WIDTH: usize = 11 + 2 + 2; // フィールド＋壁＋番兵
 pub const FIELD_HEIGHT: usize = 20 + 1 + 1; // フィールド＋底＋番兵
 pub type Field = [[BlockColor; FIELD_WIDTH]; FIELD_HEIGHT];
 
-// ネクストブロックを3つ表示
-pub const NEXT_LENGTH: usize = 3;
+// 何も置かれていないフィールド
+pub const EMPTY_FIELD: Field = [
+    [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
+    [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
+    [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
+    [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
+    [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
+    [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
+    [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
+    [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
+    [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
+    [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
+    [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
+    [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
+    [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
+    [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
+    [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
+    [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
+    [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
+    [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
+    [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
+    [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
+    [0, W, W, W, W, W, W, W, W, W, W, W, W, W, 0],
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+];
+
+// でかブロックのフィールドの大きさ
+// 1マスを2×2で描画するため、フィールドの左上に幅5×高さ10のフィールドを作り、残りを壁で埋める
+pub const BIG_WIDTH: usize = (FIELD_WIDTH - 4) / 2;
+pub const BIG_HEIGHT: usize = (FIELD_HEIGHT - 2) / 2;
+
+// 何も置かれていないでかブロックのフィールド
+pub const BIG_FIELD: Field = {
+    let mut field = [[W; FIELD_WIDTH]; FIELD_HEIGHT];
+    let mut y = 0;
+    while y < BIG_HEIGHT {
+        let mut x = 2;
+        while x < 2 + BIG_WIDTH {
+            field[y][x] = block_kind::NONE;
+            x += 1;
+        }
+        y += 1;
+    }
+    field
+};
+
+// ネクストキューに保つブロックの数(ネクストの最大表示数)
+pub const NEXT_MAX: usize = 7;
+
+// ルール
+// CLI版と同じく設定ファイルで変更できる
+// 設定ファイルでは`preset`で選んだプリセットを元に、書いた項目だけを変更する
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(from = "RulesetTable")]
+pub struct Ruleset {
+    pub next: usize,            // ネクストの表示数(0～`NEXT_MAX`)
+    pub hold: bool,             // ホールドを使えるか
+    pub hold_reset: bool,       // ホールドしたブロックを出現時の向きに戻すか
+    pub are: u64, // ブロックを固定してから次のブロックを操作できるまでの時間(ミリ秒、ARE)
+    pub line_clear_delay: u64, // ラインを消した場合に`are`に加える時間(ミリ秒)
+    pub ghost: bool, // ゴーストを表示するか
+    pub hard_drop: bool, // ハードドロップを使えるか
+    pub kicks: bool, // 回転できない場合に位置をずらして回転するか(スーパーローテーション)
+    pub randomizer: Randomizer, // ブロックの生成方式
+    pub gravity: Gravity, // 自然落下の速さ
+    pub scoring: Scoring, // 得点の計算方法
+    pub invisible: bool, // 固定したブロックを見えなくするか(インビジブル)
+    pub invisible_delay: u64, // 固定してから見えなくなるまでの時間(ミリ秒)
+    pub big: bool, // 1マスを2×2で描画する小さなフィールドで遊ぶか(でかブロック)
+}
+
+impl Default for Ruleset {
+    fn default() -> Ruleset {
+        Ruleset::preset(Preset::Guideline)
+    }
+}
+
+impl Ruleset {
+    // プリセットのルール
+    pub fn preset(preset: Preset) -> Ruleset {
+        match preset {
+            Preset::Guideline => Ruleset {
+                next: 3,
+                hold: true,
+                hold_reset: false,
+                are: 0,
+                line_clear_delay: 0,
+                ghost: true,
+                hard_drop: true,
+                kicks: true,
+                randomizer: Randomizer::Bag7,
+                gravity: Gravity::Guideline,
+                scoring: Scoring::Guideline,
+                invisible: false,
+                invisible_delay: 0,
+                big: false,
+            },
+            // NES版の出現待ちは10～18フレーム、ライン消去は17～20フレーム
+            Preset::Nes => Ruleset {
+                next: 1,
+                hold: false,
+                hold_reset: false,
+                are: 167,
+                line_clear_delay: 300,
+                ghost: false,
+                hard_drop: false,
+                kicks: false,
+                randomizer: Randomizer::Nes,
+                gravity: Gravity::Nes,
+                scoring: Scoring::Nes,
+                invisible: false,
+                invisible_delay: 0,
+                big: false,
+            },
+        }
+    }
+}
+
+// ルールのプリセット
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Preset {
+    #[default]
+    Guideline, // 現代的なルール(7種1巡、ホールド、ゴースト、ハードドロップ、スーパーローテーション)
+    Nes, // NES版のルール(ホールド、ゴースト、ハードドロップ、回転時の位置ずらしなし)
+}
+
+// 設定ファイルに書くルールの表
+// 書いていない項目はプリセットの値を使う
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesetTable {
+    #[serde(default)]
+    preset: Preset,
+    next: Option<usize>,
+    hold: Option<bool>,
+    hold_reset: Option<bool>,
+    are: Option<u64>,
+    line_clear_delay: Option<u64>,
+    ghost: Option<bool>,
+    hard_drop: Option<bool>,
+    kicks: Option<bool>,
+    randomizer: Option<Randomizer>,
+    gravity: Option<Gravity>,
+    scoring: Option<Scoring>,
+    invisible: Option<bool>,
+    invisible_delay: Option<u64>,
+    big: Option<bool>,
+}
+
+impl From<RulesetTable> for Ruleset {
+    fn from(table: RulesetTable) -> Ruleset {
+        let rules = Ruleset::preset(table.preset);
+        Ruleset {
+            next: table.next.unwrap_or(rules.next),
+            hold: table.hold.unwrap_or(rules.hold),
+            hold_reset: table.hold_reset.unwrap_or(rules.hold_reset),
+            are: table.are.unwrap_or(rules.are),
+            line_clear_delay: table.line_clear_delay.unwrap_or(rules.line_clear_delay),
+            ghost: table.ghost.unwrap_or(rules.ghost),
+            hard_drop: table.hard_drop.unwrap_or(rules.hard_drop),
+            kicks: table.kicks.unwrap_or(rules.kicks),
+            randomizer: table.randomizer.unwrap_or(rules.randomizer),
+            gravity: table.gravity.unwrap_or(rules.gravity),
+            scoring: table.scoring.unwrap_or(rules.scoring),
+            invisible: table.invisible.unwrap_or(rules.invisible),
+            invisible_delay: table.invisible_delay.unwrap_or(rules.invisible_delay),
+            big: table.big.unwrap_or(rules.big),
+        }
+    }
+}
+
+// NES版のレベルごとの落下速度(1マス落下するまでのフレーム数、レベル29以降は1フレーム)
+#[rustfmt::skip]
+const NES_GRAVITY_FRAMES: [u64; 30] = [
+    48, 43, 38, 33, 28, 23, 18, 13, 8, 6,
+    5, 5, 5, 4, 4, 4, 3, 3, 3, 2,
+    2, 2, 2, 2, 2, 2, 2, 2, 2, 1,
+];
+
+// 自然落下の速さ
+// レベルは消したライン数10ごとに1つ上がる
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Gravity {
+    Guideline, // 1秒から始まり、レベルごとに100ミリ秒ずつ速くなる(最速100ミリ秒)
+    Nes,       // NES版の落下速度表(60フレーム/秒)
+}
+
+impl Gravity {
+    // 消したライン数に応じた自然落下の間隔(ミリ秒)
+    pub fn msec(self, line: usize) -> u64 {
+        let level = line / 10;
+        match self {
+            Gravity::Guideline => match 1000u64.saturating_sub(level as u64 * 100) {
+                0 => 100,
+                msec => msec,
+            },
+            Gravity::Nes => NES_GRAVITY_FRAMES[level.min(NES_GRAVITY_FRAMES.len() - 1)] * 1000 / 60,
+        }
+    }
+}
+
+// NES版の得点表(レベル+1を掛ける)
+pub const NES_SCORE_TABLE: [usize; 5] = [
+    0,    // 0段消し
+    40,   // 1段消し
+    100,  // 2段消し
+    300,  // 3段消し
+    1200, // 4段消し
+];
+
+// 得点の計算方法
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scoring {
+    Guideline, // `SCORE_TABLE`の得点
+    Nes,       // `NES_SCORE_TABLE`の得点にレベル+1を掛ける
+}
+
+impl Scoring {
+    // 消したライン数(`line`は消す前の合計)に応じた得点
+    pub fn score(self, lines: usize, line: usize) -> usize {
+        match self {
+            Scoring::Guideline => SCORE_TABLE[lines],
+            Scoring::Nes => NES_SCORE_TABLE[lines] * (line / 10 + 1),
+        }
+    }
+}
+
+// 出現待ち(ARE)の状態
+// 出現待ちの間に入力した回転(IRS)とホールド(IHS)は出現時にまとめて適用する
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Entry {
+    pub delay: u64,   // 出現までの時間(ミリ秒)
+    pub turns: usize, // 入力した右回転の数(4で割った余り)
+    pub hold: bool,   // ホールドを入力したか
+}
 
 #[derive(Clone, Copy)]
 pub struct Position {
@@ -32,6 +279,26 @@ impl Position {
     pub const fn init() -> Position {
         Position { x: 5, y: 0 }
     }
+
+    // ルールに合わせたブロックの出現位置
+    // でかブロックでは幅5のフィールドの中央に出現する
+    pub fn spawn(rules: &Ruleset) -> Position {
+        if rules.big {
+            Position { x: 3, y: 0 }
+        } else {
+            Position::init()
+        }
+    }
+}
+
+// ゲーム内で発生したイベント
+// 統計などはこのイベントを受け取って処理する
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    // ホールド
+    Hold,
+    // ブロックの固定(消したライン数、Tスピンか、パーフェクトクリアか)
+    Lock { lines: usize, tspin: bool, pc: bool },
 }
 
 #[derive(Resource)]
@@ -41,52 +308,50 @@ pub struct Game {
     pub block: BlockShape,
     pub hold: Option<BlockShape>,
     pub holded: bool,
+    pub rotated: bool, // 最後の移動が回転か(Tスピン判定用)
     pub next: VecDeque<BlockShape>,
     pub next_buf: VecDeque<BlockShape>,
     pub score: usize,
     pub line: usize,
+    pub rules: Ruleset,
+    pub entry: Option<Entry>, // 出現待ちの状態(出現待ちでなければ`None`)
+    pub events: Vec<Event>,
+    pub lock_ids: Field, // マスを固定したブロックの番号(インビジブルで見えなくなるまで待つマスだけ、1始まり)
+    pub locks: usize,    // 固定したブロックの数
+    pub faded: usize,    // 見えなくなったブロックの数(`lock_ids`がこれ以下のマスは見えない)
 }
 
 impl Game {
-    pub fn new() -> Game {
+    // ルールを指定してゲームを生成する
+    // ブロックの生成方式もルールに従う
+    pub fn with_rules(rules: Ruleset) -> Game {
         let mut game = Game {
-            field: [
-                [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
-                [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
-                [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
-                [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
-                [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
-                [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
-                [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
-                [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
-                [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
-                [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
-                [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
-                [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
-                [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
-                [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
-                [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
-                [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
-                [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
-                [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
-                [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
-                [0, W, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, W, 0],
-                [0, W, W, W, W, W, W, W, W, W, W, W, W, W, 0],
-                [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
-            ],
-            pos: Position::init(),
+            field: if rules.big { BIG_FIELD } else { EMPTY_FIELD },
+            pos: Position::spawn(&rules),
             block: BLOCKS[rand::random::<BlockKind>() as usize],
             hold: None,
             holded: false,
-            next: gen_block_7().into(),
-            next_buf: gen_block_7().into(),
+            rotated: false,
+            next: VecDeque::new(),
+            next_buf: VecDeque::new(),
             score: 0,
             line: 0,
+            rules,
+            entry: None,
+            events: vec![],
+            lock_ids: Default::default(),
+            locks: 0,
+            faded: 0,
         };
         // 初期ブロックを供給
         spawn_block(&mut game).ok();
         game
     }
+
+    // 発生したイベントを取り出す
+    pub fn take_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
+    }
 }
 
 // ゴーストの座標を返す
@@ -138,28 +403,69 @@ pub fn fix_block(
 // 消せるラインがあるなら削除し、段を下げる
 // 消したライン数を返す
 pub fn erase_line(field: &mut Field) -> usize {
-    let mut count = 0;
-    for y in 1..FIELD_HEIGHT - 2 {
-        let mut can_erase = true;
-        for x in 2..FIELD_WIDTH - 2 {
-            if field[y][x] == 0 {
-                can_erase = false;
-                break;
+    let rows = full_lines(field);
+    for &y in &rows {
+        remove_line(field, y);
+    }
+    rows.len()
+}
+
+// 消せるラインの段を上から順に返す
+// 壁だけの段(でかブロックのフィールドの外)は消さない
+pub fn full_lines(field: &Field) -> Vec<usize> {
+    (1..FIELD_HEIGHT - 2)
+        .filter(|&y| {
+            let row = &field[y][2..FIELD_WIDTH - 2];
+            row.iter().all(|&b| b != block_kind::NONE) && row.iter().any(|&b| b != block_kind::WALL)
+        })
+        .collect()
+}
+
+// `y`段目を削除し、上の段を下げる
+fn remove_line(field: &mut Field, y: usize) {
+    for y2 in (2..=y).rev() {
+        field[y2] = field[y2 - 1];
+    }
+}
+
+// 描画用フィールドを画面に表示するフィールドに変換する
+// - インビジブルでは見えなくなった固定済みのマスを空白にする
+// - でかブロックでは1マスを2×2に広げ、残った右端の列は壁にする
+#[allow(clippy::needless_range_loop)]
+pub fn display_field(game: &Game, field_buf: &Field) -> Field {
+    let mut field_buf = *field_buf;
+    if game.rules.invisible {
+        for y in 0..FIELD_HEIGHT - 2 {
+            for x in 2..FIELD_WIDTH - 2 {
+                let locked = !matches!(game.field[y][x], block_kind::NONE | block_kind::WALL);
+                if locked && game.lock_ids[y][x] <= game.faded {
+                    field_buf[y][x] = block_kind::NONE;
+                }
             }
         }
-        if can_erase {
-            count += 1;
-            for y2 in (2..=y).rev() {
-                field[y2] = field[y2 - 1];
-            }
+    }
+    if !game.rules.big {
+        return field_buf;
+    }
+    let mut big = EMPTY_FIELD;
+    for y in 0..BIG_HEIGHT * 2 {
+        for x in 0..FIELD_WIDTH - 4 {
+            big[y][x + 2] = if x < BIG_WIDTH * 2 {
+                field_buf[y / 2][x / 2 + 2]
+            } else {
+                W
+            };
         }
     }
-    count
+    big
 }
 
 // ブロックを指定した座標へ移動できるなら移動する
 pub fn move_block(game: &mut Game, new_pos: Position) {
     if !is_collision(&game.field, &new_pos, &game.block) {
+        if (new_pos.x, new_pos.y) != (game.pos.x, game.pos.y) {
+            game.rotated = false;
+        }
         // posの座標を更新
         game.pos = new_pos;
     }
@@ -199,9 +505,23 @@ fn super_rotation(field: &Field, pos: &Position, block: &BlockShape) -> Result<P
     Err(())
 }
 
+// 回転できない場合に位置をずらして回転できるなら、その座標を返す
+// 位置をずらさないルールでは常に`None`を返す
+fn kick(game: &Game, new_shape: &BlockShape) -> Option<Position> {
+    if !game.rules.kicks {
+        return None;
+    }
+    super_rotation(&game.field, &game.pos, new_shape).ok()
+}
+
 // 左に90度回転する
 #[allow(clippy::needless_range_loop)]
 pub fn rotate_left(game: &mut Game) {
+    if let Some(entry) = &mut game.entry {
+        // 出現待ちの間は出現時に回転する(IRS)
+        entry.turns = (entry.turns + 3) % 4;
+        return;
+    }
     let mut new_shape: BlockShape = Default::default();
     for y in 0..4 {
         for x in 0..4 {
@@ -210,15 +530,22 @@ pub fn rotate_left(game: &mut Game) {
     }
     if !is_collision(&game.field, &game.pos, &new_shape) {
         game.block = new_shape;
-    } else if let Ok(new_pos) = super_rotation(&game.field, &game.pos, &new_shape) {
+        game.rotated = true;
+    } else if let Some(new_pos) = kick(game, &new_shape) {
         game.pos = new_pos;
         game.block = new_shape;
+        game.rotated = true;
     }
 }
 
 // 右に90度回転する
 #[allow(clippy::needless_range_loop)]
 pub fn rotate_right(game: &mut Game) {
+    if let Some(entry) = &mut game.entry {
+        // 出現待ちの間は出現時に回転する(IRS)
+        entry.turns = (entry.turns + 1) % 4;
+        return;
+    }
     let mut new_shape: BlockShape = Default::default();
     for y in 0..4 {
         for x in 0..4 {
@@ -227,9 +554,35 @@ pub fn rotate_right(game: &mut Game) {
     }
     if !is_collision(&game.field, &game.pos, &new_shape) {
         game.block = new_shape;
-    } else if let Ok(new_pos) = super_rotation(&game.field, &game.pos, &new_shape) {
+        game.rotated = true;
+    } else if let Some(new_pos) = kick(game, &new_shape) {
+        game.pos = new_pos;
+        game.block = new_shape;
+        game.rotated = true;
+    }
+}
+
+// 180度回転する
+#[allow(clippy::needless_range_loop)]
+pub fn rotate_180(game: &mut Game) {
+    if let Some(entry) = &mut game.entry {
+        // 出現待ちの間は出現時に回転する(IRS)
+        entry.turns = (entry.turns + 2) % 4;
+        return;
+    }
+    let mut new_shape: BlockShape = Default::default();
+    for y in 0..4 {
+        for x in 0..4 {
+            new_shape[y][x] = game.block[4 - 1 - y][4 - 1 - x];
+        }
+    }
+    if !is_collision(&game.field, &game.pos, &new_shape) {
+        game.block = new_shape;
+        game.rotated = true;
+    } else if let Some(new_pos) = kick(game, &new_shape) {
         game.pos = new_pos;
         game.block = new_shape;
+        game.rotated = true;
     }
 }
 
@@ -243,6 +596,7 @@ pub fn hard_drop(game: &mut Game) {
         !is_collision(&game.field, &new_pos, &game.block)
     } {
         game.pos.y += 1;
+        game.rotated = false;
     }
     let new_pos = game.pos;
     move_block(game, new_pos);
@@ -251,17 +605,27 @@ pub fn hard_drop(game: &mut Game) {
 // ホールド処理
 // - 1回目のホールドは現在のブロックをホールド
 // - 2回目以降のホールドは現在のブロックとホールドを交換
-// - 現在のブロックに対して既にホールドしている場合は何もしない
+// - 現在のブロックに対して既にホールドしている場合やホールドを使えないルールでは何もしない
+// - ホールドしたブロックの向きを戻すルールでは出現時の向きにしてからホールドする
+// - 出現待ちの間は出現時にホールドする(IHS)
 pub fn hold(game: &mut Game) {
-    if game.holded {
+    if game.holded || !game.rules.hold {
         // 現在のブロックに対して既にホールドしている場合は早期リターン
         return;
     }
+    if let Some(entry) = &mut game.entry {
+        entry.hold = true;
+        return;
+    }
+    if game.rules.hold_reset {
+        game.block = spawn_shape(&game.block);
+    }
     if let Some(mut hold) = game.hold {
         // ホールドの交換
         std::mem::swap(&mut hold, &mut game.block);
         game.hold = Some(hold);
-        game.pos = Position::init();
+        game.pos = Position::spawn(&game.rules);
+        game.rotated = false;
     } else {
         // ホールドして、新たなブロックを生成
         game.hold = Some(game.block);
@@ -269,40 +633,127 @@ pub fn hold(game: &mut Game) {
     }
     // ホールド済のフラグを立てる
     game.holded = true;
+    game.events.push(Event::Hold);
 }
 
 // ブロック落下後の処理
 pub fn landing(game: &mut Game) -> Result<(), ()> {
+    // 固定する前にTスピンか判定
+    let tspin = is_tspin(game);
     // ブロックをフィールドに固定
     fix_block(game);
-    // ラインの削除処理
+    game.locks += 1;
+    if game.rules.invisible && game.rules.invisible_delay > 0 {
+        // 見えなくなるまでの時間を待つマスとして記録する(待たないマスはすぐに見えなくなる)
+        let Game {
+            lock_ids,
+            pos,
+            block,
+            locks,
+            ..
+        } = game;
+        for y in 0..4 {
+            for x in 0..4 {
+                if block[y][x] != block_kind::NONE {
+                    lock_ids[y + pos.y][x + pos.x] = *locks;
+                }
+            }
+        }
+    }
+    // ラインの削除処理(見えなくなるまで待つマスも一緒に下げる)
+    for y in full_lines(&game.field) {
+        remove_line(&mut game.lock_ids, y);
+    }
     let line = erase_line(&mut game.field);
     // 消した段数によって得点を加算
-    game.score += SCORE_TABLE[line];
+    game.score += game.rules.scoring.score(line, game.line);
     // 消した段数の合計を加算
     game.line += line;
+    let pc = line > 0 && is_field_empty(&game.field);
+    game.events.push(Event::Lock {
+        lines: line,
+        tspin,
+        pc,
+    });
     // ブロックの生成
     spawn_block(game)?;
     // 再ホールド可能にする
     game.holded = false;
+    // 出現待ちの時間が設定されていれば、経過するまで操作できなくする
+    let delay = game.rules.are
+        + if line > 0 {
+            game.rules.line_clear_delay
+        } else {
+            0
+        };
+    if delay > 0 {
+        game.entry = Some(Entry {
+            delay,
+            turns: 0,
+            hold: false,
+        });
+    }
     Ok(())
 }
 
+// 固定したブロックを1つ見えなくする
+// 固定してから`invisible_delay`経過するごとに呼ぶ
+pub fn fade(game: &mut Game) {
+    game.faded = (game.faded + 1).min(game.locks);
+}
+
+// 出現待ちを終えてブロックを操作できるようにする
+// 出現待ちの間に入力したホールド(IHS)と回転(IRS)をこの順に適用する
+pub fn enter(game: &mut Game) {
+    let Some(Entry {
+        turns,
+        hold: do_hold,
+        ..
+    }) = game.entry.take()
+    else {
+        return;
+    };
+    if do_hold {
+        hold(game);
+    }
+    match turns {
+        1 => rotate_right(game),
+        2 => rotate_180(game),
+        3 => rotate_left(game),
+        _ => (),
+    }
+}
+
+// ブロックの生成方式に従ってネクストキューの末尾にブロックを1つ供給する
+fn supply_next(game: &mut Game) {
+    let block = match game.rules.randomizer {
+        Randomizer::Bag7 => {
+            if game.next_buf.is_empty() {
+                // バフを生成
+                game.next_buf = gen_block_7().into();
+            }
+            // バフから供給
+            game.next_buf.pop_front().unwrap()
+        }
+        Randomizer::Nes => gen_block_nes(game.next.back()),
+    };
+    game.next.push_back(block);
+}
+
 // ブロックを生成する
 // 生成に失敗した場合は`Err(())`を返す
 pub fn spawn_block(game: &mut Game) -> Result<(), ()> {
     // posの座標を初期値へ
-    game.pos = Position::init();
-    // ネクストキューから次のブロックを取り出す
+    game.pos = Position::spawn(&game.rules);
+    game.rotated = false;
+    // ネクストキューから次のブロックを取り出す(生成直後はネクストキューが空)
+    if game.next.is_empty() {
+        supply_next(game);
+    }
     game.block = game.next.pop_front().unwrap();
-    if let Some(next) = game.next_buf.pop_front() {
-        // バフからネクストキューに供給
-        game.next.push_back(next);
-    } else {
-        // バフを生成
-        game.next_buf = gen_block_7().into();
-        // バフからネクストキューに供給
-        game.next.push_back(game.next_buf.pop_front().unwrap());
+    // ネクストキューが`NEXT_MAX`個になるまで供給
+    while game.next.len() < NEXT_MAX {
+        supply_next(game);
     }
     // 衝突チェック
     if is_collision(&game.field, &game.pos, &game.block) {
@@ -311,3 +762,59 @@ pub fn spawn_block(game: &mut Game) -> Result<(), ()> {
         Ok(())
     }
 }
+
+// Tスピンなら`true`を返す
+// Tブロックの最後の移動が回転で、中心の斜め4マスのうち3マス以上が埋まっている場合はTスピン
+fn is_tspin(
+    Game {
+        field,
+        pos,
+        block,
+        rotated,
+        ..
+    }: &Game,
+) -> bool {
+    if !rotated || !block.iter().flatten().any(|&b| b == block_kind::T) {
+        return false;
+    }
+    let filled = |x: isize, y: isize| {
+        (0..4).contains(&x)
+            && (0..4).contains(&y)
+            && block[y as usize][x as usize] != block_kind::NONE
+    };
+    // 中心は上下左右のうち3方向にブロックがあるマス
+    let Some((cx, cy)) = (0..4)
+        .flat_map(|y| (0..4).map(move |x| (x, y)))
+        .find(|&(x, y)| {
+            filled(x, y)
+                && [(0, -1), (1, 0), (0, 1), (-1, 0)]
+                    .iter()
+                    .filter(|(dx, dy)| filled(x + dx, y + dy))
+                    .count()
+                    == 3
+        })
+    else {
+        return false;
+    };
+    // 斜め4マスのうち埋まっているマスを数える(フィールド外は埋まっているとみなす)
+    let corners = [(-1, -1), (1, -1), (-1, 1), (1, 1)]
+        .iter()
+        .filter(|(dx, dy)| {
+            let x = pos.x as isize + cx + dx;
+            let y = pos.y as isize + cy + dy;
+            !(0..FIELD_WIDTH as isize).contains(&x)
+                || !(0..FIELD_HEIGHT as isize).contains(&y)
+                || field[y as usize][x as usize] != block_kind::NONE
+        })
+        .count();
+    corners >= 3
+}
+
+// ブロックが何もないか(パーフェクトクリア判定用)
+fn is_field_empty(field: &Field) -> bool {
+    field[..FIELD_HEIGHT - 2].iter().all(|row| {
+        row[2..FIELD_WIDTH - 2]
+            .iter()
+            .all(|&b| b == block_kind::NONE || b == block_kind::WALL)
+    })
+}
@@ -1,5 +1,7 @@
 mod block;
 mod game;
+mod stats;
 
 pub use block::*;
 pub use game::*;
+pub use stats::*;
@@ -0,0 +1,148 @@
+use bevy::prelude::Resource;
+
+use super::game::{Event, GARBAGE_TABLE};
+use std::time::{Duration, Instant};
+
+// 1ゲームの統計
+// エンジンのイベントを受け取って集計する
+#[derive(Resource)]
+pub struct Stats {
+    start: Instant,             // 開始時刻
+    paused: Duration,           // 一時停止していた時間の合計
+    paused_at: Option<Instant>, // 一時停止中なら一時停止した時刻
+    pub pieces: usize,          // 置いたブロックの数
+    pub keys: usize,            // 入力したキーの数
+    pub lines: usize,           // 消したライン数
+    pub clears: [usize; 5],     // 消したライン数ごとの回数(1段消し～4段消し)
+    pub tspins: usize,          // Tスピンの回数
+    pub attack: usize,          // 攻撃力(対戦時に送るおじゃまライン数)の合計
+    pub max_combo: usize,       // 最大コンボ数
+    pub b2b: usize,             // Back-to-Backの回数
+    pub pcs: usize,             // パーフェクトクリアの回数
+    pub holds: usize,           // ホールドの回数
+    streak: usize,              // 連続でラインを消したブロックの数
+    b2b_ready: bool,            // 直前のライン消去がテトリスかTスピンか
+}
+
+impl Stats {
+    pub fn new() -> Stats {
+        Stats {
+            start: Instant::now(),
+            paused: Duration::ZERO,
+            paused_at: None,
+            pieces: 0,
+            keys: 0,
+            lines: 0,
+            clears: [0; 5],
+            tspins: 0,
+            attack: 0,
+            max_combo: 0,
+            b2b: 0,
+            pcs: 0,
+            holds: 0,
+            streak: 0,
+            b2b_ready: false,
+        }
+    }
+
+    // キー入力を記録する
+    pub fn input(&mut self) {
+        self.keys += 1;
+    }
+
+    // イベントを記録する
+    pub fn record(&mut self, event: &Event) {
+        match *event {
+            Event::Hold => {
+                self.holds += 1;
+            }
+            Event::Lock { lines, tspin, pc } => {
+                self.pieces += 1;
+                self.lines += lines;
+                self.clears[lines] += 1;
+                self.attack += GARBAGE_TABLE[lines];
+                if tspin {
+                    self.tspins += 1;
+                }
+                if pc {
+                    self.pcs += 1;
+                }
+                if lines == 0 {
+                    // ラインを消さなかったらコンボは途切れる
+                    self.streak = 0;
+                    return;
+                }
+                // 2回目以降の連続したライン消去がコンボ
+                self.streak += 1;
+                self.max_combo = self.max_combo.max(self.streak - 1);
+                // テトリスかTスピンが続けばBack-to-Back
+                let difficult = lines == 4 || tspin;
+                if difficult && self.b2b_ready {
+                    self.b2b += 1;
+                }
+                self.b2b_ready = difficult;
+            }
+        }
+    }
+
+    // 現在のコンボ数
+    pub fn combo(&self) -> usize {
+        self.streak.saturating_sub(1)
+    }
+
+    // プレイ時間(一時停止していた時間を除く)
+    pub fn elapsed(&self) -> Duration {
+        let paused = self.paused + self.paused_at.map_or(Duration::ZERO, |at| at.elapsed());
+        self.start.elapsed().saturating_sub(paused)
+    }
+
+    // プレイ時間の計測を一時停止する
+    pub fn pause(&mut self) {
+        self.paused_at.get_or_insert_with(Instant::now);
+    }
+
+    // プレイ時間の計測を再開する
+    pub fn resume(&mut self) {
+        if let Some(at) = self.paused_at.take() {
+            self.paused += at.elapsed();
+        }
+    }
+
+    // 1秒あたりに置いたブロックの数
+    pub fn pps(&self) -> f64 {
+        self.pieces as f64 / self.elapsed().as_secs_f64().max(1.)
+    }
+
+    // 1分あたりの攻撃力
+    pub fn apm(&self) -> f64 {
+        self.attack as f64 * 60. / self.elapsed().as_secs_f64().max(1.)
+    }
+
+    // 1ブロックあたりのキー入力数
+    pub fn kpp(&self) -> f64 {
+        self.keys as f64 / self.pieces.max(1) as f64
+    }
+
+    // 表示用の項目と値
+    pub fn summary(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("pieces", self.pieces.to_string()),
+            ("PPS", format!("{:.2}", self.pps())),
+            ("APM", format!("{:.1}", self.apm())),
+            ("KPP", format!("{:.2}", self.kpp())),
+            ("lines", self.lines.to_string()),
+            ("single", self.clears[1].to_string()),
+            ("double", self.clears[2].to_string()),
+            ("triple", self.clears[3].to_string()),
+            ("tetris", self.clears[4].to_string()),
+            ("T-spin", self.tspins.to_string()),
+            (
+                "combo",
+                format!("{} (max {})", self.combo(), self.max_combo),
+            ),
+            ("B2B", self.b2b.to_string()),
+            ("PC", self.pcs.to_string()),
+            ("holds", self.holds.to_string()),
+        ]
+    }
+}
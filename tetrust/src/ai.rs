@@ -4,11 +4,17 @@ use crate::ga::{GenomeKind, GenoSeq};
 
 // 評価して、一番優秀な個体を返す
 pub fn eval(game: &Game, weight: &GenoSeq) -> Game {
+    // 出現待ちは待たずに出現させる
+    let mut game = game.clone();
+    enter(&mut game);
+    let game = &game;
+    let size = field_size(&game.rules);
     // エリートブロック (Game, score)
     let mut elite = (game.clone(), 0f64);
 
-    // ホールド有無
-    for do_hold in [true, false] {
+    // ホールド有無(ホールドを使えないルールではホールドしない場合のみ)
+    let holds: &[bool] = if game.rules.hold { &[true, false] } else { &[false] };
+    for &do_hold in holds {
         let mut game = game.clone();
         if do_hold {
             hold(&mut game);
@@ -37,9 +43,9 @@ pub fn eval(game: &Game, weight: &GenoSeq) -> Game {
 
                 // インプット情報の取得
                 let line        = erase_line_count(&game.field);  // 消せるライン数
-                let height_max  = field_height_max(&game.field);  // フィールドの高さ
-                let height_diff = diff_in_height(&game.field);    // 高低差
-                let dead_space  = dead_space_count(&game.field);  // デッドスペース数
+                let height_max  = field_height_max(&game.field, size);  // フィールドの高さ
+                let height_diff = diff_in_height(&game.field, size);    // 高低差
+                let dead_space  = dead_space_count(&game.field, size);  // デッドスペース数
 
                 // 正規化
                 let mut line        =       normalization(line as f64, 0.0, 4.0);
@@ -71,34 +77,30 @@ fn normalization(value: f64, min: f64, max: f64) -> f64 {
     (value - min) / (max - min)
 }
 
+// 評価するフィールドの幅と高さを返す
+// でかブロックでは壁で囲んだ左上の部分だけを評価する
+fn field_size(rules: &Ruleset) -> (usize, usize) {
+    if rules.big {
+        (BIG_WIDTH, BIG_HEIGHT)
+    } else {
+        (FIELD_WIDTH-4, FIELD_HEIGHT-2)
+    }
+}
+
 // 消せるライン数を返す
-#[allow(clippy::needless_range_loop)]
 fn erase_line_count(field: &Field) -> usize {
-    let mut count = 0;
-    for y in 1..FIELD_HEIGHT-2 {
-        let mut can_erase = true;
-        for x in 2..FIELD_WIDTH-2 {
-            if field[y][x] == block_kind::NONE {
-                can_erase = false;
-                break;
-            }
-        }
-        if can_erase {
-            count += 1;
-        }
-    }
-    count
+    full_lines(field).len()
 }
 
 // フィールドの一番高いブロックの高さを返す
 // ブロックが何もないときは「0」
 // ブロックが積みあがっていくにつれ、数値は増える
 #[allow(clippy::needless_range_loop)]
-fn field_height_max(field: &Field) -> usize {
-    for y in 1..FIELD_HEIGHT-2 {
-        for x in 2..FIELD_WIDTH-2 {
+fn field_height_max(field: &Field, (width, height): (usize, usize)) -> usize {
+    for y in 1..height {
+        for x in 2..2+width {
             if field[y][x] != block_kind::NONE {
-                return FIELD_HEIGHT - y - 1;
+                return height - y + 1;
             }
         }
     }
@@ -107,30 +109,31 @@ fn field_height_max(field: &Field) -> usize {
 
 // フィールドの高低差の合計を返す
 #[allow(clippy::needless_range_loop)]
-pub fn diff_in_height(field: &Field) -> usize {
+pub fn diff_in_height(field: &Field, (width, height): (usize, usize)) -> usize {
     let mut diff = 0;
     let mut top = [0; FIELD_WIDTH-4];
     // 各列の一番上の高さを求める
-    for x in 2..FIELD_WIDTH-2 {
-        for y in 1..FIELD_HEIGHT-2 {
+    for x in 2..2+width {
+        for y in 1..height {
             if field[y][x] != block_kind::NONE {
-                top[x-2] = FIELD_HEIGHT - y - 1;
+                top[x-2] = height - y + 1;
                 break;
             }
         }
     }
     // 右隣との差を合計する
-    for i in 0..FIELD_WIDTH-4-1 {
+    for i in 0..width-1 {
         diff += top[i].abs_diff(top[i+1]);
     }
     diff
 }
 
 // デッドスペース数を返す
-pub fn dead_space_count(field: &Field) -> usize {
+#[allow(clippy::needless_range_loop)]
+pub fn dead_space_count(field: &Field, (width, height): (usize, usize)) -> usize {
     let mut count = 0;
-    for y in (1..FIELD_HEIGHT-2).rev() {
-        for x in 2..FIELD_WIDTH-2 {
+    for y in (1..height).rev() {
+        for x in 2..2+width {
             // 各列の一番下の何もない座標
             if field[y][x] == block_kind::NONE {
                 for y2 in (2..y).rev() {
@@ -145,3 +148,48 @@ pub fn dead_space_count(field: &Field) -> usize {
     }
     count
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation;
+
+    #[test]
+    fn test_field_features() {
+        let field = notation::field("
+            X.X........
+            XX.X.......
+            XXXX.XXXXXX
+        ");
+        let size = field_size(&Ruleset::default());
+        assert_eq!(erase_line_count(&field), 0);
+        assert_eq!(field_height_max(&field, size), 4);
+        assert_eq!(diff_in_height(&field, size), 8);
+        assert_eq!(dead_space_count(&field, size), 1);
+
+        let field = notation::field("
+            ....I......
+            XXXXIXXXXXX
+        ");
+        assert_eq!(erase_line_count(&field), 1);
+    }
+
+    #[test]
+    fn test_eval_big() {
+        // でかブロックでは右側の壁を積み上がったブロックとして数えない
+        let rules = Ruleset { big: true, ..Ruleset::default() };
+        let size = field_size(&rules);
+        let mut field = BIG_FIELD;
+        field[BIG_HEIGHT-1][2] = block_kind::GARBAGE;
+        assert_eq!(field_height_max(&field, size), 2);
+        assert_eq!(diff_in_height(&field, size), 2);
+        assert_eq!(dead_space_count(&field, size), 0);
+        // 右端の列にばかり積み上げず、50個置いても積み上がらない
+        let mut game = Game::with_seed_and_rules(0, rules);
+        for _ in 0..50 {
+            game = eval(&game, &crate::ga::DEFAULT_GENOME);
+            landing(&mut game).unwrap();
+        }
+        assert!(game.field[..BIG_HEIGHT].iter().any(|row| row[BIG_WIDTH+1] == block_kind::NONE));
+    }
+}
@@ -1,9 +1,9 @@
 use rand::{
     distributions::{Distribution, Standard},
     seq::SliceRandom,
-    thread_rng,
-    Rng,
+    Rng, RngCore,
 };
+use serde::{Serialize, Deserialize};
 use block_kind::{I, O, S, Z, J, L, T};
 
 pub type BlockColor = usize;
@@ -19,10 +19,12 @@ pub mod block_kind {
     pub const J:     super::BlockColor = 7;
     pub const L:     super::BlockColor = 8;
     pub const T:     super::BlockColor = 9;
+    pub const GARBAGE: super::BlockColor = 10;
+    pub const DIM_I: super::BlockColor = 11;  // 薄い色のゴースト(描画用、ここから7つがI～Tと同じ順)
 }
 
 // 表示ブロックの色/文字定義
-pub const COLOR_TABLE: [&str; 10] = [
+pub const COLOR_TABLE: [&str; 18] = [
     "\x1b[48;2;000;000;000m  ",  // 何もなし
     "\x1b[48;2;127;127;127m__",  // 壁
     "\x1b[48;2;000;000;000m[]",  // ゴースト
@@ -33,8 +35,266 @@ pub const COLOR_TABLE: [&str; 10] = [
     "\x1b[48;2;255;000;255m__",  // J
     "\x1b[48;2;255;127;000m__",  // L
     "\x1b[48;2;255;255;000m__",  // T
+    "\x1b[48;2;080;080;080m__",  // おじゃま
+    "\x1b[48;2;000;000;090m[]",  // ゴースト(薄いI)
+    "\x1b[48;2;000;090;000m[]",  // ゴースト(薄いO)
+    "\x1b[48;2;000;090;090m[]",  // ゴースト(薄いS)
+    "\x1b[48;2;090;000;000m[]",  // ゴースト(薄いZ)
+    "\x1b[48;2;090;000;090m[]",  // ゴースト(薄いJ)
+    "\x1b[48;2;090;045;000m[]",  // ゴースト(薄いL)
+    "\x1b[48;2;090;090;000m[]",  // ゴースト(薄いT)
 ];
 
+// 淡い配色テーマの色/文字定義
+pub const PASTEL_COLOR_TABLE: [&str; 18] = [
+    "\x1b[48;2;000;000;000m  ",  // 何もなし
+    "\x1b[48;2;160;160;176m__",  // 壁
+    "\x1b[48;2;000;000;000m[]",  // ゴースト
+    "\x1b[48;2;128;200;255m__",  // I
+    "\x1b[48;2;255;240;150m__",  // O
+    "\x1b[48;2;150;230;150m__",  // S
+    "\x1b[48;2;255;150;150m__",  // Z
+    "\x1b[48;2;150;160;255m__",  // J
+    "\x1b[48;2;255;200;140m__",  // L
+    "\x1b[48;2;210;160;240m__",  // T
+    "\x1b[48;2;100;100;110m__",  // おじゃま
+    "\x1b[48;2;045;070;090m[]",  // ゴースト(薄いI)
+    "\x1b[48;2;090;084;052m[]",  // ゴースト(薄いO)
+    "\x1b[48;2;052;080;052m[]",  // ゴースト(薄いS)
+    "\x1b[48;2;090;052;052m[]",  // ゴースト(薄いZ)
+    "\x1b[48;2;052;056;090m[]",  // ゴースト(薄いJ)
+    "\x1b[48;2;090;070;049m[]",  // ゴースト(薄いL)
+    "\x1b[48;2;074;056;084m[]",  // ゴースト(薄いT)
+];
+
+// 白黒の配色テーマの色/文字定義
+pub const MONO_COLOR_TABLE: [&str; 18] = [
+    "\x1b[48;2;000;000;000m  ",  // 何もなし
+    "\x1b[48;2;127;127;127m__",  // 壁
+    "\x1b[48;2;000;000;000m[]",  // ゴースト
+    "\x1b[48;2;224;224;224m__",  // I
+    "\x1b[48;2;224;224;224m__",  // O
+    "\x1b[48;2;224;224;224m__",  // S
+    "\x1b[48;2;224;224;224m__",  // Z
+    "\x1b[48;2;224;224;224m__",  // J
+    "\x1b[48;2;224;224;224m__",  // L
+    "\x1b[48;2;224;224;224m__",  // T
+    "\x1b[48;2;080;080;080m__",  // おじゃま
+    "\x1b[48;2;078;078;078m[]",  // ゴースト(薄いI)
+    "\x1b[48;2;078;078;078m[]",  // ゴースト(薄いO)
+    "\x1b[48;2;078;078;078m[]",  // ゴースト(薄いS)
+    "\x1b[48;2;078;078;078m[]",  // ゴースト(薄いZ)
+    "\x1b[48;2;078;078;078m[]",  // ゴースト(薄いJ)
+    "\x1b[48;2;078;078;078m[]",  // ゴースト(薄いL)
+    "\x1b[48;2;078;078;078m[]",  // ゴースト(薄いT)
+];
+
+// 256色の端末用の色/文字定義
+pub const COLOR_TABLE_256: [&str; 18] = [
+    "\x1b[48;5;016m  ",  // 何もなし
+    "\x1b[48;5;244m__",  // 壁
+    "\x1b[48;5;016m[]",  // ゴースト
+    "\x1b[48;5;021m__",  // I
+    "\x1b[48;5;046m__",  // O
+    "\x1b[48;5;051m__",  // S
+    "\x1b[48;5;196m__",  // Z
+    "\x1b[48;5;201m__",  // J
+    "\x1b[48;5;208m__",  // L
+    "\x1b[48;5;226m__",  // T
+    "\x1b[48;5;239m__",  // おじゃま
+    "\x1b[48;5;017m[]",  // ゴースト(薄いI)
+    "\x1b[48;5;022m[]",  // ゴースト(薄いO)
+    "\x1b[48;5;023m[]",  // ゴースト(薄いS)
+    "\x1b[48;5;052m[]",  // ゴースト(薄いZ)
+    "\x1b[48;5;053m[]",  // ゴースト(薄いJ)
+    "\x1b[48;5;094m[]",  // ゴースト(薄いL)
+    "\x1b[48;5;058m[]",  // ゴースト(薄いT)
+];
+
+// 256色の端末用の淡い配色テーマの色/文字定義
+pub const PASTEL_COLOR_TABLE_256: [&str; 18] = [
+    "\x1b[48;5;016m  ",  // 何もなし
+    "\x1b[48;5;247m__",  // 壁
+    "\x1b[48;5;016m[]",  // ゴースト
+    "\x1b[48;5;117m__",  // I
+    "\x1b[48;5;228m__",  // O
+    "\x1b[48;5;114m__",  // S
+    "\x1b[48;5;210m__",  // Z
+    "\x1b[48;5;111m__",  // J
+    "\x1b[48;5;222m__",  // L
+    "\x1b[48;5;183m__",  // T
+    "\x1b[48;5;241m__",  // おじゃま
+    "\x1b[48;5;024m[]",  // ゴースト(薄いI)
+    "\x1b[48;5;058m[]",  // ゴースト(薄いO)
+    "\x1b[48;5;022m[]",  // ゴースト(薄いS)
+    "\x1b[48;5;052m[]",  // ゴースト(薄いZ)
+    "\x1b[48;5;017m[]",  // ゴースト(薄いJ)
+    "\x1b[48;5;094m[]",  // ゴースト(薄いL)
+    "\x1b[48;5;053m[]",  // ゴースト(薄いT)
+];
+
+// 256色の端末用の白黒の配色テーマの色/文字定義
+pub const MONO_COLOR_TABLE_256: [&str; 18] = [
+    "\x1b[48;5;016m  ",  // 何もなし
+    "\x1b[48;5;244m__",  // 壁
+    "\x1b[48;5;016m[]",  // ゴースト
+    "\x1b[48;5;253m__",  // I
+    "\x1b[48;5;253m__",  // O
+    "\x1b[48;5;253m__",  // S
+    "\x1b[48;5;253m__",  // Z
+    "\x1b[48;5;253m__",  // J
+    "\x1b[48;5;253m__",  // L
+    "\x1b[48;5;253m__",  // T
+    "\x1b[48;5;239m__",  // おじゃま
+    "\x1b[48;5;238m[]",  // ゴースト(薄いI)
+    "\x1b[48;5;238m[]",  // ゴースト(薄いO)
+    "\x1b[48;5;238m[]",  // ゴースト(薄いS)
+    "\x1b[48;5;238m[]",  // ゴースト(薄いZ)
+    "\x1b[48;5;238m[]",  // ゴースト(薄いJ)
+    "\x1b[48;5;238m[]",  // ゴースト(薄いL)
+    "\x1b[48;5;238m[]",  // ゴースト(薄いT)
+];
+
+// 16色の端末用の色/文字定義
+// 16色では薄い色を使えないため、薄い色のゴーストはブロックと同じ色で`[]`を描く
+pub const COLOR_TABLE_16: [&str; 18] = [
+    "\x1b[40m  ",  // 何もなし
+    "\x1b[47m__",  // 壁
+    "\x1b[40m[]",  // ゴースト
+    "\x1b[44m__",  // I
+    "\x1b[42m__",  // O
+    "\x1b[46m__",  // S
+    "\x1b[41m__",  // Z
+    "\x1b[45m__",  // J
+    "\x1b[43m__",  // L
+    "\x1b[103m__",  // T
+    "\x1b[100m__",  // おじゃま
+    "\x1b[44m[]",  // ゴースト(薄いI)
+    "\x1b[42m[]",  // ゴースト(薄いO)
+    "\x1b[46m[]",  // ゴースト(薄いS)
+    "\x1b[41m[]",  // ゴースト(薄いZ)
+    "\x1b[45m[]",  // ゴースト(薄いJ)
+    "\x1b[43m[]",  // ゴースト(薄いL)
+    "\x1b[103m[]",  // ゴースト(薄いT)
+];
+
+// 16色の端末用の淡い配色テーマの色/文字定義
+pub const PASTEL_COLOR_TABLE_16: [&str; 18] = [
+    "\x1b[40m  ",  // 何もなし
+    "\x1b[47m__",  // 壁
+    "\x1b[40m[]",  // ゴースト
+    "\x1b[106m__",  // I
+    "\x1b[103m__",  // O
+    "\x1b[102m__",  // S
+    "\x1b[101m__",  // Z
+    "\x1b[104m__",  // J
+    "\x1b[43m__",  // L
+    "\x1b[105m__",  // T
+    "\x1b[100m__",  // おじゃま
+    "\x1b[106m[]",  // ゴースト(薄いI)
+    "\x1b[103m[]",  // ゴースト(薄いO)
+    "\x1b[102m[]",  // ゴースト(薄いS)
+    "\x1b[101m[]",  // ゴースト(薄いZ)
+    "\x1b[104m[]",  // ゴースト(薄いJ)
+    "\x1b[43m[]",  // ゴースト(薄いL)
+    "\x1b[105m[]",  // ゴースト(薄いT)
+];
+
+// 16色の端末用の白黒の配色テーマの色/文字定義
+pub const MONO_COLOR_TABLE_16: [&str; 18] = [
+    "\x1b[40m  ",  // 何もなし
+    "\x1b[47m__",  // 壁
+    "\x1b[40m[]",  // ゴースト
+    "\x1b[107m__",  // I
+    "\x1b[107m__",  // O
+    "\x1b[107m__",  // S
+    "\x1b[107m__",  // Z
+    "\x1b[107m__",  // J
+    "\x1b[107m__",  // L
+    "\x1b[107m__",  // T
+    "\x1b[100m__",  // おじゃま
+    "\x1b[107m[]",  // ゴースト(薄いI)
+    "\x1b[107m[]",  // ゴースト(薄いO)
+    "\x1b[107m[]",  // ゴースト(薄いS)
+    "\x1b[107m[]",  // ゴースト(薄いZ)
+    "\x1b[107m[]",  // ゴースト(薄いJ)
+    "\x1b[107m[]",  // ゴースト(薄いL)
+    "\x1b[107m[]",  // ゴースト(薄いT)
+];
+
+// 色を使わない端末用の文字定義(ブロックの種類ごとの文字)
+pub const ASCII_TABLE: [&str; 18] = [
+    " .",  // 何もなし
+    "##",  // 壁
+    "[]",  // ゴースト
+    "II",  // I
+    "OO",  // O
+    "SS",  // S
+    "ZZ",  // Z
+    "JJ",  // J
+    "LL",  // L
+    "TT",  // T
+    "XX",  // おじゃま
+    "ii",  // ゴースト(薄いI)
+    "oo",  // ゴースト(薄いO)
+    "ss",  // ゴースト(薄いS)
+    "zz",  // ゴースト(薄いZ)
+    "jj",  // ゴースト(薄いJ)
+    "ll",  // ゴースト(薄いL)
+    "tt",  // ゴースト(薄いT)
+];
+
+// ブロックの色に対応する薄い色のゴーストを返す
+pub fn dim_color(color: BlockColor) -> BlockColor {
+    color - I + block_kind::DIM_I
+}
+
+// ブロックの種類を表す文字
+pub fn block_char(color: BlockColor) -> char {
+    match color {
+        block_kind::NONE    => '.',
+        block_kind::WALL    => '#',
+        block_kind::GHOST   => '+',
+        block_kind::I       => 'I',
+        block_kind::O       => 'O',
+        block_kind::S       => 'S',
+        block_kind::Z       => 'Z',
+        block_kind::J       => 'J',
+        block_kind::L       => 'L',
+        block_kind::T       => 'T',
+        _                   => 'X',  // おじゃま
+    }
+}
+
+// 文字に対応するブロックの形状を返す
+pub fn char_shape(c: char) -> Option<BlockShape> {
+    let kind = match c.to_ascii_uppercase() {
+        'I' => BlockKind::I,
+        'O' => BlockKind::O,
+        'S' => BlockKind::S,
+        'Z' => BlockKind::Z,
+        'J' => BlockKind::J,
+        'L' => BlockKind::L,
+        'T' => BlockKind::T,
+        _ => return None,
+    };
+    Some(BLOCKS[kind as usize])
+}
+
+// ブロックの形状からブロックの種類(色)を返す
+pub fn shape_color(shape: &BlockShape) -> BlockColor {
+    shape.iter()
+        .flatten()
+        .copied()
+        .find(|&b| b != block_kind::NONE)
+        .unwrap_or(block_kind::NONE)
+}
+
+// 回転したブロックの形状から出現時の向きの形状を返す
+pub fn spawn_shape(shape: &BlockShape) -> BlockShape {
+    BLOCKS[shape_color(shape) - block_kind::I]
+}
+
 // ブロックの種類
 const BLOCK_KIND_MAX: usize = 7;
 #[derive(Clone, Copy)]
@@ -116,9 +376,61 @@ pub const BLOCKS: [BlockShape; BLOCK_KIND_MAX] = [
     ],
 ];
 
+// ブロックの生成方式
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Randomizer {
+    Bag7,  // 7種1巡
+    Nes,   // NES版と同じ方式(直前と同じブロックなら1回だけ引き直す)
+}
+
+// ブロック生成用の乱数生成器(xorshift64)
+// 同じシード値からは常に同じ順番でブロックが生成される
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BlockRng {
+    state: u64,
+}
+
+impl BlockRng {
+    pub fn new(seed: u64) -> BlockRng {
+        // シード値が0だと乱数が0のまま変化しないため、splitmix64で撹拌する
+        let mut z = seed.wrapping_add(0x9e3779b97f4a7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^= z >> 31;
+        BlockRng {
+            state: if z == 0 { 0x9e3779b97f4a7c15 } else { z },
+        }
+    }
+}
+
+impl RngCore for BlockRng {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
 // シャッフルされた7種のブロックを生成
-pub fn gen_block_7() -> [BlockShape; BLOCK_KIND_MAX] {
-    let mut rng = thread_rng();
+pub fn gen_block_7(rng: &mut BlockRng) -> [BlockShape; BLOCK_KIND_MAX] {
     let mut que = [
         BlockKind::I,
         BlockKind::O,
@@ -128,6 +440,16 @@ pub fn gen_block_7() -> [BlockShape; BLOCK_KIND_MAX] {
         BlockKind::L,
         BlockKind::T,
     ];
-    que.shuffle(&mut rng);
+    que.shuffle(rng);
     que.map(|block| BLOCKS[block as usize])
 }
+
+// NES版と同じ方式でブロックを1つ生成
+// 7種と「引き直し」の8通りから選び、引き直しか直前と同じブロックなら7種から1回だけ引き直す
+pub fn gen_block_nes(rng: &mut BlockRng, prev: Option<&BlockShape>) -> BlockShape {
+    let roll = rng.gen_range(0..=7);
+    if roll < BLOCK_KIND_MAX && prev.map(shape_color) != Some(roll + block_kind::I) {
+        return BLOCKS[roll];
+    }
+    BLOCKS[rng.gen::<BlockKind>() as usize]
+}
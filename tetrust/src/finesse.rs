@@ -0,0 +1,202 @@
+use std::collections::{HashSet, VecDeque};
+use crate::block::{BlockShape, block_kind};
+use crate::game::{Game, Position, Operation, is_collision, operate, hard_drop};
+
+// 最小入力数を探索する上限
+const MAX_INPUTS: usize = 8;
+
+// 最小入力数の探索で使う入力
+// 横移動のリピート(DAS)で壁まで移動するのは1入力とみなす
+#[derive(Clone, Copy)]
+enum Input {
+    Op(Operation),
+    DasLeft,
+    DasRight,
+}
+
+// 180度回転はガイドラインのフィネスに含まれないため使わない
+const INPUTS: [Input; 6] = [
+    Input::Op(Operation::MoveLeft),
+    Input::Op(Operation::MoveRight),
+    Input::Op(Operation::RotateLeft),
+    Input::Op(Operation::RotateRight),
+    Input::DasLeft,
+    Input::DasRight,
+];
+
+// ブロックが占めるマス
+// 回転後の形が同じブロック(Oなど)を区別しないようマスの集合で比較する
+#[allow(clippy::needless_range_loop)]
+fn cells(pos: &Position, block: &BlockShape) -> Vec<(usize, usize)> {
+    let mut cells = vec![];
+    for y in 0..4 {
+        for x in 0..4 {
+            if block[y][x] != block_kind::NONE {
+                cells.push((pos.x+x, pos.y+y));
+            }
+        }
+    }
+    cells
+}
+
+// 出現した時点の状態から、ハードドロップで`target`の位置に置くまでの最小入力数を返す
+// ハードドロップ自体は数えない
+// 探索の上限までに見つからない場合(ずらし入れなど)は`None`を返す
+pub fn min_inputs(start: &Game, target: &[(usize, usize)]) -> Option<usize> {
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::from([(start.clone(), 0)]);
+    visited.insert((start.pos.x, start.pos.y, start.block));
+    while let Some((game, depth)) = queue.pop_front() {
+        // この位置からハードドロップした場合の位置
+        let mut dropped = game.clone();
+        hard_drop(&mut dropped);
+        if cells(&dropped.pos, &dropped.block) == target {
+            return Some(depth);
+        }
+        if depth == MAX_INPUTS {
+            continue;
+        }
+        for input in INPUTS {
+            let mut next = game.clone();
+            match input {
+                Input::Op(op) => {
+                    operate(&mut next, op);
+                }
+                Input::DasLeft | Input::DasRight => {
+                    let op = if let Input::DasLeft = input { Operation::MoveLeft } else { Operation::MoveRight };
+                    // 移動できなくなるまで移動する
+                    while {
+                        let x = next.pos.x;
+                        operate(&mut next, op);
+                        next.pos.x != x
+                    } {}
+                }
+            }
+            if visited.insert((next.pos.x, next.pos.y, next.block)) {
+                queue.push_back((next, depth + 1));
+            }
+        }
+    }
+    None
+}
+
+// フィネス(最小入力数での配置)の判定
+pub struct Finesse {
+    start: Game,                       // 操作中のブロックが出現した時点の状態
+    inputs: Vec<(Operation, bool)>,    // 操作中のブロックへの入力(横移動後に壁などに接しているか)
+    soft_drop: bool,                   // ソフトドロップしたか(ずらし入れなどは判定しない)
+    pub pieces: usize,                 // 判定したブロックの数
+    pub faults: usize,                 // 最小入力数より多く入力したブロックの数
+    pub last: Option<(usize, usize)>,  // 直前に判定したブロックの入力数と最小入力数
+}
+
+impl Finesse {
+    pub fn new(game: &Game) -> Finesse {
+        Finesse {
+            start: game.clone(),
+            inputs: vec![],
+            soft_drop: false,
+            pieces: 0,
+            faults: 0,
+            last: None,
+        }
+    }
+
+    // 新たに出現したブロックから判定をやり直す
+    pub fn reset(&mut self, game: &Game) {
+        self.start = game.clone();
+        self.inputs.clear();
+        self.soft_drop = false;
+    }
+
+    // 操作を記録する
+    // 操作を適用した後に呼び出す
+    pub fn record(&mut self, op: Operation, game: &Game) {
+        match op {
+            Operation::MoveLeft | Operation::MoveRight => {
+                let x = if op == Operation::MoveLeft { game.pos.x.saturating_sub(1) } else { game.pos.x + 1 };
+                let blocked = is_collision(&game.field, &Position { x, y: game.pos.y }, &game.block);
+                self.inputs.push((op, blocked));
+            }
+            Operation::RotateLeft | Operation::RotateRight | Operation::Rotate180 => {
+                self.inputs.push((op, false));
+            }
+            Operation::SoftDrop => {
+                self.soft_drop = true;
+            }
+            Operation::Hold => {
+                // ホールドで出現したブロックから数え直す
+                self.reset(game);
+            }
+            Operation::HardDrop | Operation::Gravity | Operation::Entry | Operation::Fade => (),
+        }
+    }
+
+    // 入力数
+    // 同じ方向への連続した横移動は、壁などに接して止まった場合はリピート(DAS)として1入力とみなす
+    fn input_count(&self) -> usize {
+        let mut count = 0;
+        let mut i = 0;
+        while i < self.inputs.len() {
+            let (op, _) = self.inputs[i];
+            if op == Operation::MoveLeft || op == Operation::MoveRight {
+                let len = self.inputs[i..].iter().take_while(|(o, _)| *o == op).count();
+                let (_, blocked) = self.inputs[i+len-1];
+                count += if blocked { 1 } else { len };
+                i += len;
+            } else {
+                count += 1;
+                i += 1;
+            }
+        }
+        count
+    }
+
+    // ブロックを固定する直前に呼び出す
+    // 固定する位置への最小入力数と比較する
+    pub fn lock(&mut self, game: &Game) {
+        if self.soft_drop {
+            return;
+        }
+        let Some(min) = min_inputs(&self.start, &cells(&game.pos, &game.block)) else {
+            return;
+        };
+        let count = self.input_count();
+        self.pieces += 1;
+        if count > min {
+            self.faults += 1;
+        }
+        self.last = Some((count, min));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation;
+
+    // `ops`で操作して固定したときの入力数と最小入力数
+    fn judge(queue: &str, ops: &[Operation]) -> (usize, usize) {
+        let mut game = Game::with_seed(0).with_setup(notation::parse(&format!("queue: {queue}")).unwrap());
+        let mut finesse = Finesse::new(&game);
+        for &op in ops {
+            operate(&mut game, op);
+            finesse.record(op, &game);
+        }
+        operate(&mut game, Operation::HardDrop);
+        finesse.lock(&game);
+        finesse.last.unwrap()
+    }
+
+    #[test]
+    fn test_finesse() {
+        use Operation::*;
+        // 壁までの移動はリピート1回
+        assert_eq!(judge("O", &[MoveLeft; 5]), (1, 1));
+        // 壁の手前は壁まで移動して戻す
+        assert_eq!(judge("O", &[MoveLeft, MoveLeft, MoveLeft]), (3, 2));
+        // 左回転1回で済むところを右回転3回
+        assert_eq!(judge("T", &[RotateRight, RotateRight, RotateRight]), (3, 1));
+        assert_eq!(judge("T", &[RotateLeft]), (1, 1));
+    }
+}
@@ -0,0 +1,386 @@
+use crate::block::{block_kind, block_char, char_shape, shape_color, BlockColor, BlockShape};
+use crate::game::{Game, Setup, Field, EMPTY_FIELD, FIELD_WIDTH, FIELD_HEIGHT};
+
+// fumen(テト譜)の変換
+// v115形式の1ページ目の読み込みと、複数ページの書き出しに対応する
+//
+// テト譜のフィールドは幅10列のため、このゲームのフィールド(幅11列)とは次のように対応させる
+// - 読み込み時: 左10列に配置し、ブロックがある段は右端の列をおじゃまブロックで埋める
+// - 書き出し時: 右端の列は出力しない
+
+// テト譜のバージョン
+const VERSION: &str = "v115@";
+// 1文字で表す値(64進数)
+const ENCODE_TABLE: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+// コメントに使用できる文字
+const COMMENT_TABLE: &[u8] = b" !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~";
+
+// テト譜のフィールドサイズ
+const FUMEN_WIDTH:  usize = 10;
+const FUMEN_HEIGHT: usize = 23 + 1;  // フィールド＋せり上がり用の段
+const FUMEN_BLOCKS: usize = FUMEN_WIDTH * FUMEN_HEIGHT;
+// テト譜の上から何段目がこのゲームのフィールドの最上段か
+const TOP_OFFSET: usize = 23 - (FIELD_HEIGHT - 2);
+
+// テト譜のブロックの種類
+// 0: なし, 1: I, 2: L, 3: O, 4: Z, 5: T, 6: J, 7: S, 8: おじゃま
+fn to_fumen(color: BlockColor) -> u32 {
+    match color {
+        block_kind::NONE | block_kind::GHOST => 0,
+        block_kind::I => 1,
+        block_kind::L => 2,
+        block_kind::O => 3,
+        block_kind::Z => 4,
+        block_kind::T => 5,
+        block_kind::J => 6,
+        block_kind::S => 7,
+        _ => 8,
+    }
+}
+
+fn from_fumen(value: u32) -> BlockColor {
+    match value {
+        0 => block_kind::NONE,
+        1 => block_kind::I,
+        2 => block_kind::L,
+        3 => block_kind::O,
+        4 => block_kind::Z,
+        5 => block_kind::T,
+        6 => block_kind::J,
+        7 => block_kind::S,
+        _ => block_kind::GARBAGE,
+    }
+}
+
+// テト譜の1ページ目を読み込む
+// コメントに`#Q=[ホールド](操作中のブロック)ネクスト`形式の指定があればホールドとネクストも読み込む
+pub fn decode(fumen: &str) -> Result<Setup, String> {
+    let data = fumen
+        .split_once(VERSION)
+        .map(|(_, data)| data)
+        .ok_or_else(|| format!("unsupported fumen (only {VERSION} is supported)"))?;
+    let mut reader = Reader::new(data)?;
+
+    // フィールド(前ページとの差分をランレングス圧縮したもの)
+    // 1ページ目なので前ページは空のフィールド
+    let mut cells = [0; FUMEN_BLOCKS];
+    let mut i = 0;
+    while i < FUMEN_BLOCKS {
+        let value = reader.poll(2)?;
+        let diff = value / FUMEN_BLOCKS as u32;
+        let count = (value % FUMEN_BLOCKS as u32) as usize + 1;
+        if diff < 8 || i + count > FUMEN_BLOCKS {
+            return Err("broken field data".to_string());
+        }
+        cells[i..i+count].fill(diff - 8);
+        i += count;
+        if count == FUMEN_BLOCKS && diff == 8 {
+            // 変化のないページの繰り返し数
+            reader.poll(1)?;
+        }
+    }
+
+    // ページの操作情報
+    let action = reader.poll(3)?;
+    let has_comment = (action / (8 * 4 * FUMEN_BLOCKS as u32 * 8)) % 2 == 1;
+    let comment = if has_comment {
+        let len = reader.poll(2)? as usize;
+        let mut comment = vec![];
+        while comment.len() < len {
+            let mut value = reader.poll(5)?;
+            for _ in 0..4 {
+                let index = (value % (COMMENT_TABLE.len() as u32 + 1)) as usize;
+                comment.push(*COMMENT_TABLE.get(index).unwrap_or(&b' '));
+                value /= COMMENT_TABLE.len() as u32 + 1;
+            }
+        }
+        comment.truncate(len);
+        unescape(&String::from_utf8_lossy(&comment))
+    } else {
+        String::new()
+    };
+
+    // フィールドの変換
+    let mut field = EMPTY_FIELD;
+    for (y, row) in cells.chunks(FUMEN_WIDTH).take(23).enumerate() {
+        if row.iter().all(|&b| b == 0) {
+            continue;
+        }
+        if y < TOP_OFFSET {
+            return Err("blocks above the visible field are not supported".to_string());
+        }
+        let row_field = &mut field[y - TOP_OFFSET];
+        for (x, &b) in row.iter().enumerate() {
+            row_field[x+2] = from_fumen(b);
+        }
+        // 右端の列を埋める
+        row_field[FIELD_WIDTH-3] = block_kind::GARBAGE;
+    }
+
+    // ホールドとネクスト
+    let (hold, queue) = parse_quiz(&comment)?;
+    Ok(Setup { field, hold, queue })
+}
+
+// `#Q=[ホールド](操作中のブロック)ネクスト`形式のコメントを読み込む
+fn parse_quiz(comment: &str) -> Result<(Option<BlockShape>, Vec<BlockShape>), String> {
+    let Some(quiz) = comment.strip_prefix("#Q=") else {
+        return Ok((None, vec![]));
+    };
+    let invalid = || format!("invalid quiz comment: {comment}");
+    let (hold, rest) = quiz
+        .strip_prefix('[')
+        .and_then(|quiz| quiz.split_once(']'))
+        .ok_or_else(invalid)?;
+    let (current, next) = rest
+        .strip_prefix('(')
+        .and_then(|rest| rest.split_once(')'))
+        .ok_or_else(invalid)?;
+    let hold = match hold {
+        "" => None,
+        hold => Some(hold.chars().next().and_then(char_shape).ok_or_else(invalid)?),
+    };
+    let queue = current
+        .chars()
+        .chain(next.chars().take_while(|c| c.is_ascii_alphabetic()))
+        .map(|c| char_shape(c).ok_or_else(invalid))
+        .collect::<Result<_, _>>()?;
+    Ok((hold, queue))
+}
+
+// フィールドをテト譜(1フィールド1ページ)に変換する
+// 1ページ目にはコメントを付けられる
+pub fn encode(fields: &[Field], comment: &str) -> String {
+    let mut writer = Writer::default();
+    let mut prev = [0; FUMEN_BLOCKS];
+    for (page, field) in fields.iter().enumerate() {
+        // フィールドの変換
+        let mut cells = [0; FUMEN_BLOCKS];
+        for (y, row) in field[..FIELD_HEIGHT-2].iter().enumerate() {
+            for x in 0..FUMEN_WIDTH {
+                cells[(y + TOP_OFFSET) * FUMEN_WIDTH + x] = to_fumen(row[x+2]);
+            }
+        }
+        // 前ページとの差分をランレングス圧縮する
+        let diffs = cells.iter().zip(prev).map(|(&b, p)| b + 8 - p).collect::<Vec<_>>();
+        let mut i = 0;
+        while i < FUMEN_BLOCKS {
+            let count = diffs[i..].iter().take_while(|&&d| d == diffs[i]).count();
+            writer.push(diffs[i] * FUMEN_BLOCKS as u32 + count as u32 - 1, 2);
+            if count == FUMEN_BLOCKS && diffs[i] == 8 {
+                // 変化のないページの繰り返し数(常に0とする)
+                writer.push(0, 1);
+            }
+            i += count;
+        }
+        // ページの操作情報(ブロックは置かず、フィールドのみのページ)
+        // 上位から順に「固定しない」「コメント有無」「色付き」「反転」「せり上がり」
+        let comment = if page == 0 { escape(comment) } else { String::new() };
+        let flags = [false, !comment.is_empty(), page == 0, false, false];
+        let action = flags.iter().fold(0, |acc, &flag| acc * 2 + flag as u32) * FUMEN_BLOCKS as u32 * 4 * 8;
+        writer.push(action, 3);
+        // コメント
+        if !comment.is_empty() {
+            writer.push(comment.len() as u32, 2);
+            for chunk in comment.as_bytes().chunks(4) {
+                let value = chunk.iter().rev().fold(0, |acc, &c| {
+                    let index = COMMENT_TABLE.iter().position(|&t| t == c).unwrap_or(0) as u32;
+                    acc * (COMMENT_TABLE.len() as u32 + 1) + index
+                });
+                writer.push(value, 5);
+            }
+        }
+        // 次のページはライン消去後のフィールドとの差分になる
+        prev = clear_lines(cells);
+    }
+    writer.finish()
+}
+
+// ゲームの現在の盤面をテト譜に変換する
+// ホールドとネクストはコメントに`#Q=[ホールド](操作中のブロック)ネクスト`形式で書き出す
+pub fn encode_game(game: &Game) -> String {
+    let hold = game.hold.map(|hold| block_char(shape_color(&hold)).to_string()).unwrap_or_default();
+    let current = block_char(shape_color(&game.block));
+    let next = game.next.iter().map(|next| block_char(shape_color(next))).collect::<String>();
+    encode(&[game.field], &format!("#Q=[{hold}]({current}){next}"))
+}
+
+// テト譜の仕様(幅10列)でそろった段を消す
+fn clear_lines(cells: [u32; FUMEN_BLOCKS]) -> [u32; FUMEN_BLOCKS] {
+    let mut cleared = [0; FUMEN_BLOCKS];
+    let rows = cells[..FUMEN_BLOCKS-FUMEN_WIDTH]
+        .chunks(FUMEN_WIDTH)
+        .filter(|row| row.contains(&0))
+        .collect::<Vec<_>>();
+    // 残った段を下に詰める(せり上がり用の段はそのまま)
+    let top = FUMEN_HEIGHT - 1 - rows.len();
+    for (y, row) in rows.into_iter().enumerate() {
+        cleared[(top + y) * FUMEN_WIDTH..][..FUMEN_WIDTH].copy_from_slice(row);
+    }
+    cleared[FUMEN_BLOCKS-FUMEN_WIDTH..].copy_from_slice(&cells[FUMEN_BLOCKS-FUMEN_WIDTH..]);
+    cleared
+}
+
+// コメントのエスケープ(JavaScriptの`escape`関数と同じ形式)
+fn escape(s: &str) -> String {
+    let mut escaped = String::new();
+    for c in s.chars() {
+        match c {
+            'A'..='Z' | 'a'..='z' | '0'..='9' | '@' | '*' | '_' | '+' | '-' | '.' | '/' => escaped.push(c),
+            c if (c as u32) < 0x100 => escaped.push_str(&format!("%{:02X}", c as u32)),
+            c => {
+                let mut buf = [0; 2];
+                for unit in c.encode_utf16(&mut buf) {
+                    escaped.push_str(&format!("%u{:04X}", unit));
+                }
+            }
+        }
+    }
+    escaped
+}
+
+// コメントのアンエスケープ(JavaScriptの`unescape`関数と同じ形式)
+fn unescape(s: &str) -> String {
+    let mut units = vec![];
+    let mut rest = s;
+    while let Some(c) = rest.chars().next() {
+        let hex = |digits: &str| u16::from_str_radix(digits, 16).ok();
+        if let Some(unit) = rest.strip_prefix("%u").and_then(|r| r.get(..4)).and_then(hex) {
+            units.push(unit);
+            rest = &rest[6..];
+        } else if let Some(unit) = rest.strip_prefix('%').and_then(|r| r.get(..2)).and_then(hex) {
+            units.push(unit);
+            rest = &rest[3..];
+        } else {
+            let mut buf = [0; 2];
+            units.extend_from_slice(c.encode_utf16(&mut buf));
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    String::from_utf16_lossy(&units)
+}
+
+// テト譜のデータの読み込み
+struct Reader {
+    values: Vec<u32>,
+    pos: usize,
+}
+
+impl Reader {
+    fn new(data: &str) -> Result<Reader, String> {
+        let values = data
+            .chars()
+            .filter(|&c| c != '?')  // 区切り文字は無視する
+            .map(|c| {
+                ENCODE_TABLE
+                    .iter()
+                    .position(|&t| t as char == c)
+                    .map(|v| v as u32)
+                    .ok_or_else(|| format!("invalid character in fumen: {c}"))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Reader { values, pos: 0 })
+    }
+
+    // n文字分の値を読み込む
+    fn poll(&mut self, n: usize) -> Result<u32, String> {
+        let values = self.values
+            .get(self.pos..self.pos+n)
+            .ok_or_else(|| "unexpected end of fumen".to_string())?;
+        self.pos += n;
+        Ok(values.iter().rev().fold(0, |acc, &v| acc * 64 + v))
+    }
+}
+
+// テト譜のデータの書き出し
+#[derive(Default)]
+struct Writer {
+    data: String,
+}
+
+impl Writer {
+    // 値をn文字で書き出す
+    fn push(&mut self, mut value: u32, n: usize) {
+        for _ in 0..n {
+            self.data.push(ENCODE_TABLE[(value % 64) as usize] as char);
+            value /= 64;
+        }
+    }
+
+    // 先頭42文字、以降47文字ごとに区切り文字`?`を挟む
+    fn finish(self) -> String {
+        let chars = self.data.chars().collect::<Vec<_>>();
+        let (head, tail) = chars.split_at(chars.len().min(42));
+        let mut fumen = VERSION.to_string();
+        fumen.extend(head);
+        for chunk in tail.chunks(47) {
+            fumen.push('?');
+            fumen.extend(chunk);
+        }
+        fumen
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::{BLOCKS, BlockKind};
+
+    #[test]
+    fn test_empty_field() {
+        assert_eq!(encode(&[EMPTY_FIELD], ""), "v115@vhAAgH");
+        let setup = decode("v115@vhAAgH").unwrap();
+        assert_eq!(setup.field, EMPTY_FIELD);
+        assert!(setup.hold.is_none());
+        assert!(setup.queue.is_empty());
+    }
+
+    #[test]
+    fn test_encode_and_decode() {
+        let mut field = EMPTY_FIELD;
+        field[19][2..FIELD_WIDTH-3].fill(block_kind::GARBAGE);
+        field[19][6] = block_kind::NONE;
+        field[18][5] = block_kind::T;
+        field[18][6] = block_kind::T;
+        field[18][7] = block_kind::T;
+        field[17][6] = block_kind::T;
+        let mut second = field;
+        second[19][6] = block_kind::I;
+
+        let fumen = encode(&[field, second], "#Q=[T](I)SZ");
+        let setup = decode(&fumen).unwrap();
+        // 右端の列はブロックのある段のみ埋まる
+        field[19][FIELD_WIDTH-3] = block_kind::GARBAGE;
+        field[18][FIELD_WIDTH-3] = block_kind::GARBAGE;
+        field[17][FIELD_WIDTH-3] = block_kind::GARBAGE;
+        assert_eq!(setup.field, field);
+        assert_eq!(setup.hold, Some(BLOCKS[BlockKind::T as usize]));
+        assert_eq!(setup.queue, [BlockKind::I, BlockKind::S, BlockKind::Z].map(|k| BLOCKS[k as usize]));
+    }
+
+    #[test]
+    fn test_decode_field() {
+        // 左6列が埋まった4段
+        let setup = decode("v115@9gF8DeF8DeF8DeF8NeAgH").unwrap();
+        let mut field = EMPTY_FIELD;
+        for row in &mut field[16..20] {
+            row[2..8].fill(block_kind::GARBAGE);
+            row[FIELD_WIDTH-3] = block_kind::GARBAGE;
+        }
+        assert_eq!(setup.field, field);
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("#Q=[T](I)SZ"), "%23Q%3D%5BT%5D%28I%29SZ");
+        assert_eq!(unescape("%23Q%3D%5BT%5D%28I%29SZ"), "#Q=[T](I)SZ");
+        assert_eq!(unescape(&escape("テト譜")), "テト譜");
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        assert!(decode("v110@vhAAgH").is_err());
+        assert!(decode("v115@vh").is_err());
+        assert!(decode("v115@!!!").is_err());
+    }
+}
@@ -39,6 +39,8 @@ pub enum GenomeKind {
 
 // 遺伝子配列
 pub type GenoSeq = [u8; 4];
+// オートプレイなどで使う遺伝子配列のデフォルト
+pub const DEFAULT_GENOME: GenoSeq = [100,1,10,100];
 impl Index<GenomeKind> for GenoSeq {
     type Output = u8;
     fn index(&self, kind: GenomeKind) -> &Self::Output {
@@ -82,6 +84,8 @@ pub fn learning() {
                         if landing(&mut game).is_err() {
                             break;
                         }
+                        // 学習中はイベントを使用しないため破棄する
+                        game.events.clear();
                     }
                     // 個体の最終スコアを記録
                     ind.score = game.score;
@@ -97,8 +101,6 @@ pub fn learning() {
             .zip(next_genos)
             .for_each(|(now, next)| *now = next);
     }
-    // 学習終了
-    quit();
 }
 
 // 次世代を生成
@@ -1,9 +1,13 @@
 use std::collections::VecDeque;
+use rand::Rng;
+use serde::{Serialize, Deserialize};
 use crate::block::{
-    BlockKind, BlockShape, BLOCKS, BlockColor, block_kind, COLOR_TABLE,
+    BlockKind, BlockShape, BLOCKS, BlockColor, block_kind,
     block_kind::WALL as W,
-    gen_block_7,
+    gen_block_7, gen_block_nes, BlockRng, Randomizer, shape_color, spawn_shape, dim_color,
 };
+use crate::config::{Config, Visual, GhostStyle, Action};
+use crate::render::Renderer;
 
 // 得点表
 pub const SCORE_TABLE: [usize; 5] = [
@@ -14,15 +18,259 @@ pub const SCORE_TABLE: [usize; 5] = [
     100,  // 4段消し
 ];
 
+// 攻撃力表(対戦時に送るおじゃまライン数)
+pub const GARBAGE_TABLE: [usize; 5] = [
+    0,  // 0段消し
+    0,  // 1段消し
+    1,  // 2段消し
+    2,  // 3段消し
+    4,  // 4段消し
+];
+
 // フィールドサイズ
 pub const FIELD_WIDTH:  usize = 11 + 2 + 2;  // フィールド＋壁＋番兵
 pub const FIELD_HEIGHT: usize = 20 + 1 + 1;  // フィールド＋底＋番兵
 pub type Field = [[BlockColor; FIELD_WIDTH]; FIELD_HEIGHT];
 
-// ネクストブロックを3つ表示
-pub const NEXT_LENGTH: usize = 3;
+// 何も置かれていないフィールド
+pub const EMPTY_FIELD: Field = [
+    [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
+    [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
+    [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
+    [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
+    [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
+    [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
+    [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
+    [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
+    [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
+    [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
+    [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
+    [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
+    [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
+    [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
+    [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
+    [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
+    [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
+    [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
+    [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
+    [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
+    [0,W,W,W,W,W,W,W,W,W,W,W,W,W,0],
+    [0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],
+];
+
+// でかブロックのフィールドの大きさ
+// 1マスを2×2で描画するため、フィールドの左上に幅5×高さ10のフィールドを作り、残りを壁で埋める
+pub const BIG_WIDTH:  usize = (FIELD_WIDTH - 4) / 2;
+pub const BIG_HEIGHT: usize = (FIELD_HEIGHT - 2) / 2;
+
+// 何も置かれていないでかブロックのフィールド
+pub const BIG_FIELD: Field = {
+    let mut field = [[W; FIELD_WIDTH]; FIELD_HEIGHT];
+    let mut y = 0;
+    while y < BIG_HEIGHT {
+        let mut x = 2;
+        while x < 2 + BIG_WIDTH {
+            field[y][x] = block_kind::NONE;
+            x += 1;
+        }
+        y += 1;
+    }
+    field
+};
+
+// ネクストキューに保つブロックの数(ネクストの最大表示数)
+pub const NEXT_MAX: usize = 7;
+
+// ルール
+// モードごとに設定ファイルで変更できる
+// 設定ファイルでは`preset`で選んだプリセットを元に、書いた項目だけを変更する
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(from = "RulesetTable")]
+pub struct Ruleset {
+    pub next: usize,             // ネクストの表示数(0～`NEXT_MAX`)
+    pub hold: bool,              // ホールドを使えるか
+    pub hold_reset: bool,        // ホールドしたブロックを出現時の向きに戻すか
+    pub are: u64,                // ブロックを固定してから次のブロックを操作できるまでの時間(ミリ秒、ARE)
+    pub line_clear_delay: u64,   // ラインを消した場合に`are`に加える時間(ミリ秒)
+    pub ghost: bool,             // ゴーストを表示するか
+    pub hard_drop: bool,         // ハードドロップを使えるか
+    pub kicks: bool,             // 回転できない場合に位置をずらして回転するか(スーパーローテーション)
+    pub randomizer: Randomizer,  // ブロックの生成方式
+    pub gravity: Gravity,        // 自然落下の速さ
+    pub scoring: Scoring,        // 得点の計算方法
+    pub invisible: bool,         // 固定したブロックを見えなくするか(インビジブル)
+    pub invisible_delay: u64,    // 固定してから見えなくなるまでの時間(ミリ秒)
+    pub big: bool,               // 1マスを2×2で描画する小さなフィールドで遊ぶか(でかブロック)
+}
+
+impl Default for Ruleset {
+    fn default() -> Ruleset {
+        Ruleset::preset(Preset::Guideline)
+    }
+}
+
+impl Ruleset {
+    // プリセットのルール
+    pub fn preset(preset: Preset) -> Ruleset {
+        match preset {
+            Preset::Guideline => Ruleset {
+                next: 3,
+                hold: true,
+                hold_reset: false,
+                are: 0,
+                line_clear_delay: 0,
+                ghost: true,
+                hard_drop: true,
+                kicks: true,
+                randomizer: Randomizer::Bag7,
+                gravity: Gravity::Guideline,
+                scoring: Scoring::Guideline,
+                invisible: false,
+                invisible_delay: 0,
+                big: false,
+            },
+            // NES版の出現待ちは10～18フレーム、ライン消去は17～20フレーム
+            Preset::Nes => Ruleset {
+                next: 1,
+                hold: false,
+                hold_reset: false,
+                are: 167,
+                line_clear_delay: 300,
+                ghost: false,
+                hard_drop: false,
+                kicks: false,
+                randomizer: Randomizer::Nes,
+                gravity: Gravity::Nes,
+                scoring: Scoring::Nes,
+                invisible: false,
+                invisible_delay: 0,
+                big: false,
+            },
+        }
+    }
+}
+
+// ルールのプリセット
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Preset {
+    #[default]
+    Guideline,  // 現代的なルール(7種1巡、ホールド、ゴースト、ハードドロップ、スーパーローテーション)
+    Nes,        // NES版のルール(ホールド、ゴースト、ハードドロップ、回転時の位置ずらしなし)
+}
+
+// 設定ファイルに書くルールの表
+// 書いていない項目はプリセットの値を使う
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesetTable {
+    #[serde(default)]
+    preset: Preset,
+    next: Option<usize>,
+    hold: Option<bool>,
+    hold_reset: Option<bool>,
+    are: Option<u64>,
+    line_clear_delay: Option<u64>,
+    ghost: Option<bool>,
+    hard_drop: Option<bool>,
+    kicks: Option<bool>,
+    randomizer: Option<Randomizer>,
+    gravity: Option<Gravity>,
+    scoring: Option<Scoring>,
+    invisible: Option<bool>,
+    invisible_delay: Option<u64>,
+    big: Option<bool>,
+}
+
+impl From<RulesetTable> for Ruleset {
+    fn from(table: RulesetTable) -> Ruleset {
+        let rules = Ruleset::preset(table.preset);
+        Ruleset {
+            next:             table.next.unwrap_or(rules.next),
+            hold:             table.hold.unwrap_or(rules.hold),
+            hold_reset:       table.hold_reset.unwrap_or(rules.hold_reset),
+            are:              table.are.unwrap_or(rules.are),
+            line_clear_delay: table.line_clear_delay.unwrap_or(rules.line_clear_delay),
+            ghost:            table.ghost.unwrap_or(rules.ghost),
+            hard_drop:        table.hard_drop.unwrap_or(rules.hard_drop),
+            kicks:            table.kicks.unwrap_or(rules.kicks),
+            randomizer:       table.randomizer.unwrap_or(rules.randomizer),
+            gravity:          table.gravity.unwrap_or(rules.gravity),
+            scoring:          table.scoring.unwrap_or(rules.scoring),
+            invisible:        table.invisible.unwrap_or(rules.invisible),
+            invisible_delay:  table.invisible_delay.unwrap_or(rules.invisible_delay),
+            big:              table.big.unwrap_or(rules.big),
+        }
+    }
+}
 
-#[derive(Clone, Copy)]
+// NES版のレベルごとの落下速度(1マス落下するまでのフレーム数、レベル29以降は1フレーム)
+const NES_GRAVITY_FRAMES: [u64; 30] = [
+    48, 43, 38, 33, 28, 23, 18, 13, 8, 6,
+    5, 5, 5, 4, 4, 4, 3, 3, 3, 2,
+    2, 2, 2, 2, 2, 2, 2, 2, 2, 1,
+];
+
+// 自然落下の速さ
+// レベルは消したライン数10ごとに1つ上がる
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Gravity {
+    Guideline,  // 1秒から始まり、レベルごとに100ミリ秒ずつ速くなる(最速100ミリ秒)
+    Nes,        // NES版の落下速度表(60フレーム/秒)
+}
+
+impl Gravity {
+    // 消したライン数に応じた自然落下の間隔(ミリ秒)
+    pub fn msec(self, line: usize) -> u64 {
+        let level = line / 10;
+        match self {
+            Gravity::Guideline => match 1000u64.saturating_sub(level as u64 * 100) {
+                0 => 100,
+                msec => msec,
+            },
+            Gravity::Nes => NES_GRAVITY_FRAMES[level.min(NES_GRAVITY_FRAMES.len() - 1)] * 1000 / 60,
+        }
+    }
+}
+
+// NES版の得点表(レベル+1を掛ける)
+pub const NES_SCORE_TABLE: [usize; 5] = [
+    0,     // 0段消し
+    40,    // 1段消し
+    100,   // 2段消し
+    300,   // 3段消し
+    1200,  // 4段消し
+];
+
+// 得点の計算方法
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scoring {
+    Guideline,  // `SCORE_TABLE`の得点
+    Nes,        // `NES_SCORE_TABLE`の得点にレベル+1を掛ける
+}
+
+impl Scoring {
+    // 消したライン数(`line`は消す前の合計)に応じた得点
+    pub fn score(self, lines: usize, line: usize) -> usize {
+        match self {
+            Scoring::Guideline => SCORE_TABLE[lines],
+            Scoring::Nes => NES_SCORE_TABLE[lines] * (line / 10 + 1),
+        }
+    }
+}
+
+// 出現待ち(ARE)の状態
+// 出現待ちの間に入力した回転(IRS)とホールド(IHS)は出現時にまとめて適用する
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub delay: u64,    // 出現までの時間(ミリ秒)
+    pub turns: usize,  // 入力した右回転の数(4で割った余り)
+    pub hold: bool,    // ホールドを入力したか
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
 pub struct Position {
     pub x: usize,
     pub y: usize,
@@ -35,61 +283,161 @@ impl Position {
             y: 0,
         }
     }
+
+    // ルールに合わせたブロックの出現位置
+    // でかブロックでは幅5のフィールドの中央に出現する
+    pub fn spawn(rules: &Ruleset) -> Position {
+        if rules.big {
+            Position { x: 3, y: 0 }
+        } else {
+            Position::init()
+        }
+    }
+}
+
+// ゲーム内で発生したイベント
+// 描画や観戦配信などはこのイベントを受け取って処理する
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    // ブロックの生成
+    Spawn,
+    // ブロックの移動
+    Move,
+    // ブロックの回転
+    Rotate,
+    // ハードドロップ
+    HardDrop,
+    // ホールド
+    Hold,
+    // ブロックの固定(消したライン数、Tスピンか、パーフェクトクリアか)
+    Lock { lines: usize, tspin: bool, pc: bool },
+    // ゲームオーバー
+    GameOver,
 }
 
+// 盤面の初期配置
 #[derive(Clone)]
+pub struct Setup {
+    pub field: Field,
+    pub hold: Option<BlockShape>,
+    pub queue: Vec<BlockShape>,  // 操作するブロックとネクスト(先頭から順に出現する)
+}
+
+// ブロックに対する操作
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    HardDrop,
+    RotateLeft,
+    RotateRight,
+    Rotate180,
+    Hold,
+    Gravity,  // 自然落下
+    Entry,    // 出現待ちの終了
+    Fade,     // 固定したブロックを古い順に1つ見えなくする(インビジブル)
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Game {
     pub field: Field,
     pub pos: Position,
     pub block: BlockShape,
     pub hold: Option<BlockShape>,
     pub holded: bool,
+    pub rotated: bool,  // 最後の移動が回転か(Tスピン判定用)
     pub next:     VecDeque<BlockShape>,
     pub next_buf: VecDeque<BlockShape>,
     pub score: usize,
     pub line: usize,
+    pub seed: u64,
+    pub rng: BlockRng,
+    pub rules: Ruleset,
+    pub entry: Option<Entry>,  // 出現待ちの状態(出現待ちでなければ`None`)
+    pub lock_ids: Field,  // マスを固定したブロックの番号(インビジブルで見えなくなるまで待つマスだけ、1始まり)
+    pub locks: usize,     // 固定したブロックの数
+    pub faded: usize,     // 見えなくなったブロックの数(`lock_ids`がこれ以下のマスは見えない)
+    #[serde(skip)]
+    pub events: Vec<Event>,
 }
 
 impl Game {
     pub fn new() -> Game {
+        Game::with_seed(rand::random())
+    }
+
+    // シード値を指定してゲームを生成する
+    // 同じシード値なら同じ順番でブロックが出現する
+    pub fn with_seed(seed: u64) -> Game {
+        Game::generate(seed, Ruleset::default())
+    }
+
+    // ルールを指定してゲームを生成する
+    // ブロックの生成方式もルールに従う
+    pub fn with_rules(rules: Ruleset) -> Game {
+        Game::with_seed_and_rules(rand::random(), rules)
+    }
+
+    // シード値とルールを指定してゲームを生成する
+    pub fn with_seed_and_rules(seed: u64, rules: Ruleset) -> Game {
+        Game::generate(seed, rules)
+    }
+
+    // 初期配置を適用する
+    // 指定したブロックを出し切った後はシード値に従ってブロックが出現する
+    // でかブロックでは大きさの違う盤面は使えないので、盤面を指定した初期配置は呼び出し側で拒否する
+    // (`--fumen`/`--board`はmain.rsで、パズルは`Config::validate`で拒否している)
+    pub fn with_setup(mut self, Setup { field, hold, queue }: Setup) -> Game {
+        debug_assert!(!self.rules.big || field == EMPTY_FIELD, "a board cannot be set up with big blocks");
+        if !self.rules.big {
+            self.field = field;
+        }
+        self.hold = hold;
+        if !queue.is_empty() {
+            // 乱数で生成したブロックの前に指定したブロックを差し込む
+            self.next.push_front(self.block);
+            for block in queue.into_iter().rev() {
+                self.next.push_front(block);
+            }
+            self.block = self.next.pop_front().unwrap();
+        }
+        self
+    }
+
+    fn generate(seed: u64, rules: Ruleset) -> Game {
+        let mut rng = BlockRng::new(seed);
+        let block = BLOCKS[rng.gen::<BlockKind>() as usize];
         let mut game = Game {
-            field: [
-                [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
-                [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
-                [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
-                [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
-                [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
-                [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
-                [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
-                [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
-                [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
-                [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
-                [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
-                [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
-                [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
-                [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
-                [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
-                [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
-                [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
-                [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
-                [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
-                [0,W,0,0,0,0,0,0,0,0,0,0,0,W,0],
-                [0,W,W,W,W,W,W,W,W,W,W,W,W,W,0],
-                [0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],
-            ],
-            pos: Position::init(),
-            block: BLOCKS[rand::random::<BlockKind>() as usize],
+            field: if rules.big { BIG_FIELD } else { EMPTY_FIELD },
+            pos: Position::spawn(&rules),
+            block,
             hold: None,
             holded: false,
-            next:     gen_block_7().into(),
-            next_buf: gen_block_7().into(),
+            rotated: false,
+            next: VecDeque::new(),
+            next_buf: VecDeque::new(),
             score: 0,
             line: 0,
+            seed,
+            rng,
+            rules,
+            entry: None,
+            lock_ids: Default::default(),
+            locks: 0,
+            faded: 0,
+            events: vec![],
         };
         // 初期ブロックを供給
         spawn_block(&mut game).ok();
         game
+    }
 
+    // 発生したイベントを取り出す
+    pub fn take_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
     }
 }
 
@@ -109,61 +457,116 @@ fn ghost_pos(field: &Field, pos: &Position, block: &BlockShape) -> Position {
 }
 
 // フィールドを描画する
+// 画面には`Renderer::flush`で出力する
 #[allow(clippy::needless_range_loop)]
-pub fn draw(Game { field, pos, block, hold, holded: _, next, next_buf: _, score, .. }: &Game) {
+pub fn draw(game: &Game, visual: &Visual, renderer: &mut Renderer) {
+    let Game { field, pos, block, hold, next, score, rules, entry, .. } = game;
+    let color_table = visual.theme.color_table(visual.color);
     // 描画用フィールドの生成
     let mut field_buf = *field;
+    // 出現待ちの間は出現するブロックをフィールドに描画せず、ネクストの先頭に描画する
+    let entering = entry.is_some();
     // 描画用フィールドにゴーストブロックを書き込む
-    let ghost_pos = ghost_pos(field, pos, block);
-    for y in 0..4 {
-        for x in 0..4 {
-            if block[y][x] != block_kind::NONE {
-                field_buf[y+ghost_pos.y][x+ghost_pos.x] = block_kind::GHOST;
+    if visual.ghost && rules.ghost && !entering {
+        let ghost_pos = ghost_pos(field, pos, block);
+        for y in 0..4 {
+            for x in 0..4 {
+                if block[y][x] != block_kind::NONE {
+                    field_buf[y+ghost_pos.y][x+ghost_pos.x] = match visual.ghost_style {
+                        GhostStyle::Outline => block_kind::GHOST,
+                        GhostStyle::Dim     => dim_color(block[y][x]),
+                    };
+                }
             }
         }
     }
     // 描画用フィールドにブロックの情報を書き込む
     for y in 0..4 {
         for x in 0..4 {
-            if block[y][x] != block_kind::NONE {
+            if block[y][x] != block_kind::NONE && !entering {
                 field_buf[y+pos.y][x+pos.x] = block[y][x];
             }
         }
     }
-    // ホールドを描画
-    println!("\x1b[2;28HHOLD");  // カーソルをホールド位置に移動
-    if let Some(hold) = hold {
-        for y in 0..4 {
-            print!("\x1b[{};28H", y+3);  // カーソルを移動
-            for x in 0..4 {
-                print!("{}", COLOR_TABLE[hold[y][x]]);
+    // ホールドを描画(ホールドを使えるルールのみ)
+    if rules.hold {
+        renderer.put_text(28, 2, "HOLD");
+        if let Some(hold) = hold {
+            for y in 0..4 {
+                for x in 0..4 {
+                    renderer.put_block(28+x*2, y+3, color_table[hold[y][x]]);
+                }
             }
-            println!();
         }
     }
-    // ネクストを描画(3つ)
-    println!("\x1b[8;28HNEXT");  // カーソルをネクスト位置に移動
-    for (i, next) in next.iter().take(NEXT_LENGTH).enumerate() {
-        for y in 0..4 {
-            print!("\x1b[{};28H", i*4+y+9);  // カーソルを移動
+    // ネクストを描画(ルールで設定した数だけ)
+    // 4つ以上表示する場合は間隔を詰め、出現時の向きでブロックのある2段(1～2段目)だけを描画する
+    if rules.next > 0 {
+        renderer.put_text(28, 8, "NEXT");
+    }
+    let (pitch, rows) = match rules.next {
+        0..=3 => (4, 0..4),
+        4..=5 => (3, 1..3),
+        _     => (2, 1..3),
+    };
+    let queue = entering.then_some(block).into_iter().chain(next);
+    for (i, next) in queue.take(rules.next).enumerate() {
+        for (dy, y) in rows.clone().enumerate() {
             for x in 0..4 {
-                print!("{}", COLOR_TABLE[next[y][x]]);
+                renderer.put_block(28+x*2, i*pitch+dy+9, color_table[next[y][x]]);
             }
-            println!();
         }
     }
-    // スコアを描画
-    println!("\x1b[22;28H{}", score);  // カーソルをスコア位置に移動
+    // スコアを描画(対戦相手のスコアと同じ行)
+    renderer.put_text(28, 23, &score.to_string());
     // フィールドを描画
-    println!("\x1b[H");  // カーソルを先頭に移動
+    let field_buf = display_field(game, &field_buf);
     for y in 0..FIELD_HEIGHT-1 {
         for x in 1..FIELD_WIDTH-1 {
-            print!("{}", COLOR_TABLE[field_buf[y][x]]);
+            renderer.put_block(x*2-1, y+2, color_table[field_buf[y][x]]);
+        }
+    }
+}
+
+// 描画用フィールドを画面に表示するフィールドに変換する
+// - インビジブルでは見えなくなった固定済みのマスを空白にする
+// - でかブロックでは1マスを2×2に広げ、残った右端の列は壁にする
+#[allow(clippy::needless_range_loop)]
+pub fn display_field(game: &Game, field_buf: &Field) -> Field {
+    let mut field_buf = *field_buf;
+    if game.rules.invisible {
+        for y in 0..FIELD_HEIGHT-2 {
+            for x in 2..FIELD_WIDTH-2 {
+                let locked = !matches!(game.field[y][x], block_kind::NONE | block_kind::WALL);
+                if locked && game.lock_ids[y][x] <= game.faded {
+                    field_buf[y][x] = block_kind::NONE;
+                }
+            }
+        }
+    }
+    if !game.rules.big {
+        return field_buf;
+    }
+    let mut big = EMPTY_FIELD;
+    for y in 0..BIG_HEIGHT*2 {
+        for x in 0..FIELD_WIDTH-4 {
+            big[y][x+2] = if x < BIG_WIDTH*2 { field_buf[y/2][x/2+2] } else { W };
+        }
+    }
+    big
+}
+
+// 対戦相手のフィールドを描画する
+// 自分のフィールドと同じ`Renderer`に書き込み、まとめて出力する
+// 通常の大きさのフィールドのみ(でかブロックの対戦は`Config::validate`で拒否している)
+pub fn draw_opponent(field: &Field, score: usize, visual: &Visual, renderer: &mut Renderer) {
+    // スコアを描画
+    renderer.put_text(40, 23, &score.to_string());
+    for (y, row) in field.iter().take(FIELD_HEIGHT-1).enumerate() {
+        for (x, cell) in row[1..FIELD_WIDTH-1].iter().enumerate() {
+            renderer.put_block(40+x*2, y+2, visual.theme.color_table(visual.color)[*cell]);
         }
-        println!();
     }
-    // 色情報をリセット
-    println!("\x1b[0m");
 }
 
 // ブロックがフィールドに衝突する場合は`true`を返す
@@ -196,28 +599,66 @@ pub fn fix_block(Game { field, pos, block, .. }: &mut Game) {
 // 消せるラインがあるなら削除し、段を下げる
 // 消したライン数を返す
 pub fn erase_line(field: &mut Field) -> usize {
-    let mut count = 0;
-    for y in 1..FIELD_HEIGHT-2 {
-        let mut can_erase = true;
-        for x in 2..FIELD_WIDTH-2 {
-            if field[y][x] == 0 {
-                can_erase = false;
-                break;
-            }
-        }
-        if can_erase {
-            count += 1;
-            for y2 in (2..=y).rev() {
-                field[y2] = field[y2-1];
-            }
+    let rows = full_lines(field);
+    for &y in &rows {
+        remove_line(field, y);
+    }
+    rows.len()
+}
+
+// 消せるラインの段を上から順に返す
+// 壁だけの段(でかブロックのフィールドの外)は消さない
+pub fn full_lines(field: &Field) -> Vec<usize> {
+    (1..FIELD_HEIGHT-2)
+        .filter(|&y| {
+            let row = &field[y][2..FIELD_WIDTH-2];
+            row.iter().all(|&b| b != block_kind::NONE) && row.iter().any(|&b| b != block_kind::WALL)
+        })
+        .collect()
+}
+
+// `y`段目を削除し、上の段を下げる
+fn remove_line(field: &mut Field, y: usize) {
+    for y2 in (2..=y).rev() {
+        field[y2] = field[y2-1];
+    }
+}
+
+// おじゃまラインを下からせり上げる
+// `hole`列(0始まり)だけ空いたラインを`lines`段追加する
+// せり上げによってブロックが天井を超えた場合は`Err(())`を返す
+// 通常の大きさのフィールドのみ(でかブロックの対戦は`Config::validate`で拒否している)
+pub fn add_garbage(field: &mut Field, lines: usize, hole: usize) -> Result<(), ()> {
+    let bottom = FIELD_HEIGHT - 2;  // 底の段
+    let lines = lines.min(bottom);
+    // 押し出される段にブロックがあるならゲームオーバー
+    let overflow = field[..lines]
+        .iter()
+        .any(|row| row[2..FIELD_WIDTH-2].iter().any(|&b| b != block_kind::NONE));
+    // 全体をせり上げる
+    for y in 0..bottom-lines {
+        field[y] = field[y+lines];
+    }
+    // おじゃまラインを追加
+    for row in field.iter_mut().take(bottom).skip(bottom-lines) {
+        for (x, cell) in row.iter_mut().enumerate().take(FIELD_WIDTH-2).skip(2) {
+            *cell = if x == hole + 2 { block_kind::NONE } else { block_kind::GARBAGE };
         }
     }
-    count
+    if overflow {
+        Err(())
+    } else {
+        Ok(())
+    }
 }
 
 // ブロックを指定した座標へ移動できるなら移動する
 pub fn move_block(game: &mut Game, new_pos: Position) {
     if !is_collision(&game.field, &new_pos, &game.block) {
+        if (new_pos.x, new_pos.y) != (game.pos.x, game.pos.y) {
+            game.events.push(Event::Move);
+            game.rotated = false;
+        }
         // posの座標を更新
         game.pos = new_pos;
     }
@@ -257,9 +698,23 @@ fn super_rotation(field: &Field, pos: &Position, block: &BlockShape) -> Result<P
     Err(())
 }
 
+// 回転できない場合に位置をずらして回転できるなら、その座標を返す
+// 位置をずらさないルールでは常に`None`を返す
+fn kick(game: &Game, new_shape: &BlockShape) -> Option<Position> {
+    if !game.rules.kicks {
+        return None;
+    }
+    super_rotation(&game.field, &game.pos, new_shape).ok()
+}
+
 // 左に90度回転する
 #[allow(clippy::needless_range_loop)]
 pub fn rotate_left(game: &mut Game) {
+    if let Some(entry) = &mut game.entry {
+        // 出現待ちの間は出現時に回転する(IRS)
+        entry.turns = (entry.turns + 3) % 4;
+        return;
+    }
     let mut new_shape: BlockShape = Default::default();
     for y in 0..4 {
         for x in 0..4 {
@@ -268,15 +723,24 @@ pub fn rotate_left(game: &mut Game) {
     }
     if !is_collision(&game.field, &game.pos, &new_shape) {
         game.block = new_shape;
-    } else if let Ok(new_pos) = super_rotation(&game.field, &game.pos, &new_shape) {
+        game.rotated = true;
+        game.events.push(Event::Rotate);
+    } else if let Some(new_pos) = kick(game, &new_shape) {
         game.pos  = new_pos;
         game.block = new_shape;
+        game.rotated = true;
+        game.events.push(Event::Rotate);
     }
 }
 
 // 右に90度回転する
 #[allow(clippy::needless_range_loop)]
 pub fn rotate_right(game: &mut Game) {
+    if let Some(entry) = &mut game.entry {
+        // 出現待ちの間は出現時に回転する(IRS)
+        entry.turns = (entry.turns + 1) % 4;
+        return;
+    }
     let mut new_shape: BlockShape = Default::default();
     for y in 0..4 {
         for x in 0..4 {
@@ -285,9 +749,39 @@ pub fn rotate_right(game: &mut Game) {
     }
     if !is_collision(&game.field, &game.pos, &new_shape) {
         game.block = new_shape;
-    } else if let Ok(new_pos) = super_rotation(&game.field, &game.pos, &new_shape) {
+        game.rotated = true;
+        game.events.push(Event::Rotate);
+    } else if let Some(new_pos) = kick(game, &new_shape) {
         game.pos  = new_pos;
         game.block = new_shape;
+        game.rotated = true;
+        game.events.push(Event::Rotate);
+    }
+}
+
+// 180度回転する
+#[allow(clippy::needless_range_loop)]
+pub fn rotate_180(game: &mut Game) {
+    if let Some(entry) = &mut game.entry {
+        // 出現待ちの間は出現時に回転する(IRS)
+        entry.turns = (entry.turns + 2) % 4;
+        return;
+    }
+    let mut new_shape: BlockShape = Default::default();
+    for y in 0..4 {
+        for x in 0..4 {
+            new_shape[y][x] = game.block[4-1-y][4-1-x];
+        }
+    }
+    if !is_collision(&game.field, &game.pos, &new_shape) {
+        game.block = new_shape;
+        game.rotated = true;
+        game.events.push(Event::Rotate);
+    } else if let Some(new_pos) = kick(game, &new_shape) {
+        game.pos  = new_pos;
+        game.block = new_shape;
+        game.rotated = true;
+        game.events.push(Event::Rotate);
     }
 }
 
@@ -301,25 +795,37 @@ pub fn hard_drop(game: &mut Game) {
         !is_collision(&game.field, &new_pos, &game.block)
     }{
         game.pos.y += 1;
+        game.rotated = false;
     }
     let new_pos = game.pos;
     move_block(game, new_pos);
+    game.events.push(Event::HardDrop);
 }
 
 // ホールド処理
 // - 1回目のホールドは現在のブロックをホールド
 // - 2回目以降のホールドは現在のブロックとホールドを交換
-// - 現在のブロックに対して既にホールドしている場合は何もしない
+// - 現在のブロックに対して既にホールドしている場合やホールドを使えないルールでは何もしない
+// - ホールドしたブロックの向きを戻すルールでは出現時の向きにしてからホールドする
+// - 出現待ちの間は出現時にホールドする(IHS)
 pub fn hold(game: &mut Game) {
-    if game.holded {
+    if game.holded || !game.rules.hold {
         // 現在のブロックに対して既にホールドしている場合は早期リターン
         return;
     }
+    if let Some(entry) = &mut game.entry {
+        entry.hold = true;
+        return;
+    }
+    if game.rules.hold_reset {
+        game.block = spawn_shape(&game.block);
+    }
     if let Some(mut hold) = game.hold {
         // ホールドの交換
         std::mem::swap(&mut hold, &mut game.block);
         game.hold = Some(hold);
-        game.pos = Position::init();
+        game.pos = Position::spawn(&game.rules);
+        game.rotated = false;
     } else {
         // ホールドして、新たなブロックを生成
         game.hold = Some(game.block);
@@ -327,58 +833,430 @@ pub fn hold(game: &mut Game) {
     }
     // ホールド済のフラグを立てる
     game.holded = true;
+    game.events.push(Event::Hold);
+}
+
+// 操作を適用する
+// ブロックが接地して固定する必要がある場合は`true`を返す(ブロックの固定は呼び出し側で行う)
+// 出現待ちの間は回転とホールド以外の操作は無視する(ブロックを見えなくする操作は受け付ける)
+// ハードドロップを使えないルールではハードドロップを無視する
+pub fn operate(game: &mut Game, op: Operation) -> bool {
+    if game.entry.is_some() && !matches!(op, Operation::RotateLeft | Operation::RotateRight | Operation::Rotate180 | Operation::Hold | Operation::Entry | Operation::Fade) {
+        return false;
+    }
+    match op {
+        Operation::MoveLeft => {
+            let new_pos = Position {
+                x: game.pos.x.checked_sub(1).unwrap_or(game.pos.x),
+                y: game.pos.y,
+            };
+            move_block(game, new_pos);
+        }
+        Operation::MoveRight => {
+            let new_pos = Position {
+                x: game.pos.x + 1,
+                y: game.pos.y,
+            };
+            move_block(game, new_pos);
+        }
+        Operation::SoftDrop => {
+            let new_pos = Position {
+                x: game.pos.x,
+                y: game.pos.y + 1,
+            };
+            move_block(game, new_pos);
+        }
+        Operation::HardDrop => {
+            if !game.rules.hard_drop {
+                // ハードドロップを使えないルールでは何もしない
+                return false;
+            }
+            hard_drop(game);
+            return true;
+        }
+        Operation::RotateLeft => {
+            rotate_left(game);
+        }
+        Operation::RotateRight => {
+            rotate_right(game);
+        }
+        Operation::Rotate180 => {
+            rotate_180(game);
+        }
+        Operation::Hold => {
+            hold(game);
+        }
+        Operation::Gravity => {
+            let new_pos = Position {
+                x: game.pos.x,
+                y: game.pos.y + 1,
+            };
+            if is_collision(&game.field, &new_pos, &game.block) {
+                // 落下できないなら接地
+                return true;
+            }
+            move_block(game, new_pos);
+        }
+        Operation::Entry => {
+            enter(game);
+        }
+        Operation::Fade => {
+            game.faded = (game.faded + 1).min(game.locks);
+        }
+    }
+    false
 }
 
 // ブロック落下後の処理
 pub fn landing(game: &mut Game) -> Result<(), ()> {
+    // 固定する前にTスピンか判定
+    let tspin = is_tspin(game);
     // ブロックをフィールドに固定
     fix_block(game);
-    // ラインの削除処理
+    game.locks += 1;
+    if game.rules.invisible && game.rules.invisible_delay > 0 {
+        // 見えなくなるまでの時間を待つマスとして記録する(待たないマスはすぐに見えなくなる)
+        let Game { lock_ids, pos, block, locks, .. } = game;
+        for y in 0..4 {
+            for x in 0..4 {
+                if block[y][x] != block_kind::NONE {
+                    lock_ids[y+pos.y][x+pos.x] = *locks;
+                }
+            }
+        }
+    }
+    // ラインの削除処理(見えなくなるまで待つマスも一緒に下げる)
+    for y in full_lines(&game.field) {
+        remove_line(&mut game.lock_ids, y);
+    }
     let line = erase_line(&mut game.field);
     // 消した段数によって得点を加算
-    game.score += SCORE_TABLE[line];
+    game.score += game.rules.scoring.score(line, game.line);
     // 消した段数の合計を加算
     game.line += line;
+    let pc = line > 0 && is_field_empty(&game.field);
+    game.events.push(Event::Lock { lines: line, tspin, pc });
     // ブロックの生成
     spawn_block(game)?;
     // 再ホールド可能にする
     game.holded = false;
+    // 出現待ちの時間が設定されていれば、経過するまで操作できなくする
+    let delay = game.rules.are + if line > 0 { game.rules.line_clear_delay } else { 0 };
+    if delay > 0 {
+        game.entry = Some(Entry { delay, turns: 0, hold: false });
+    }
     Ok(())
 }
 
+// 固定したブロックをすべて見えなくする
+// 見えなくなるまでの時間を数えないモード(オートプレイ、パズル)で使う
+pub fn fade_all(game: &mut Game) {
+    game.faded = game.locks;
+}
+
+// 出現待ちを終えてブロックを操作できるようにする
+// 出現待ちの間に入力したホールド(IHS)と回転(IRS)をこの順に適用する
+pub fn enter(game: &mut Game) {
+    let Some(Entry { turns, hold: do_hold, .. }) = game.entry.take() else {
+        return;
+    };
+    if do_hold {
+        hold(game);
+    }
+    match turns {
+        1 => rotate_right(game),
+        2 => rotate_180(game),
+        3 => rotate_left(game),
+        _ => (),
+    }
+}
+
+// Tスピンなら`true`を返す
+// Tブロックの最後の移動が回転で、中心の斜め4マスのうち3マス以上が埋まっている場合はTスピン
+fn is_tspin(Game { field, pos, block, rotated, .. }: &Game) -> bool {
+    if !rotated || shape_color(block) != block_kind::T {
+        return false;
+    }
+    let filled = |x: isize, y: isize| {
+        (0..4).contains(&x) && (0..4).contains(&y) && block[y as usize][x as usize] != block_kind::NONE
+    };
+    // 中心は上下左右のうち3方向にブロックがあるマス
+    let Some((cx, cy)) = (0..4)
+        .flat_map(|y| (0..4).map(move |x| (x, y)))
+        .find(|&(x, y)| {
+            filled(x, y) && [(0, -1), (1, 0), (0, 1), (-1, 0)].iter().filter(|(dx, dy)| filled(x+dx, y+dy)).count() == 3
+        })
+    else {
+        return false;
+    };
+    // 斜め4マスのうち埋まっているマスを数える(フィールド外は埋まっているとみなす)
+    let corners = [(-1, -1), (1, -1), (-1, 1), (1, 1)]
+        .iter()
+        .filter(|(dx, dy)| {
+            let x = pos.x as isize + cx + dx;
+            let y = pos.y as isize + cy + dy;
+            !(0..FIELD_WIDTH as isize).contains(&x)
+                || !(0..FIELD_HEIGHT as isize).contains(&y)
+                || field[y as usize][x as usize] != block_kind::NONE
+        })
+        .count();
+    corners >= 3
+}
+
+// ブロックが何もないか(パーフェクトクリア判定用)
+// でかブロックのフィールドの外の壁は何もないとみなす
+fn is_field_empty(field: &Field) -> bool {
+    field[..FIELD_HEIGHT-2]
+        .iter()
+        .all(|row| row[2..FIELD_WIDTH-2].iter().all(|&b| b == block_kind::NONE || b == block_kind::WALL))
+}
+
+// ブロックの生成方式に従ってネクストキューの末尾にブロックを1つ供給する
+fn supply_next(game: &mut Game) {
+    let block = match game.rules.randomizer {
+        Randomizer::Bag7 => {
+            if game.next_buf.is_empty() {
+                // バフを生成
+                game.next_buf = gen_block_7(&mut game.rng).into();
+            }
+            // バフから供給
+            game.next_buf.pop_front().unwrap()
+        }
+        Randomizer::Nes => gen_block_nes(&mut game.rng, game.next.back()),
+    };
+    game.next.push_back(block);
+}
+
 // ブロックを生成する
 // 生成に失敗した場合は`Err(())`を返す
 pub fn spawn_block(game: &mut Game) -> Result<(), ()> {
     // posの座標を初期値へ
-    game.pos = Position::init();
-    // ネクストキューから次のブロックを取り出す
+    game.pos = Position::spawn(&game.rules);
+    game.rotated = false;
+    // ネクストキューから次のブロックを取り出す(生成直後はネクストキューが空)
+    if game.next.is_empty() {
+        supply_next(game);
+    }
     game.block = game.next.pop_front().unwrap();
-    if let Some(next) = game.next_buf.pop_front() {
-        // バフからネクストキューに供給
-        game.next.push_back(next);
-    } else {
-        // バフを生成
-        game.next_buf = gen_block_7().into();
-        // バフからネクストキューに供給
-        game.next.push_back(game.next_buf.pop_front().unwrap());
+    // ネクストキューが`NEXT_MAX`個になるまで供給
+    while game.next.len() < NEXT_MAX {
+        supply_next(game);
     }
     // 衝突チェック
     if is_collision(&game.field, &game.pos, &game.block) {
+        game.events.push(Event::GameOver);
         Err(())
     } else {
+        game.events.push(Event::Spawn);
         Ok(())
     }
 }
 
 // ゲームオーバー処理
-pub fn gameover(game: &Game) {
-    draw(game);
-    println!("GAMEOVER");
-    println!("press `q` key to exit");
+// 画面には`Renderer::flush`で出力する
+pub fn gameover(game: &Game, config: &Config, renderer: &mut Renderer) {
+    draw(game, &config.visual, renderer);
+    renderer.put_text(1, 24, "GAMEOVER");
+    renderer.put_text(1, 25, &format!("press `{}` key to exit", config.keys.label(Action::Quit)));
 }
 
-// 終了処理
-pub fn quit() {
-    // カーソルを再表示
-    println!("\x1b[?25h");
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::{self, format_field};
+
+    #[test]
+    fn test_erase_line() {
+        let mut field = notation::field("
+            ..T........
+            XTTXXXXXXXX
+            XXTXXXXXXX.
+            XXXXXXXXXXX
+        ");
+        assert_eq!(erase_line(&mut field), 2);
+        assert_eq!(format_field(&field), "..T........\nXXTXXXXXXX.\n");
+    }
+
+    #[test]
+    fn test_is_collision() {
+        let field = notation::field("
+            ...........
+            X.X........
+            ...XXXXXXXX
+        ");
+        let t = BLOCKS[BlockKind::T as usize];
+        // 凸型の突起が隙間に収まる
+        assert!(!is_collision(&field, &Position { x: 2, y: 17 }, &t));
+        assert!(is_collision(&field, &Position { x: 3, y: 17 }, &t));
+        // 壁と床
+        assert!(is_collision(&field, &Position { x: 1, y: 17 }, &t));
+        assert!(is_collision(&field, &Position { x: 9, y: 18 }, &t));
+    }
+
+    #[test]
+    fn test_rotate_kick() {
+        let setup = notation::parse("
+            queue: T
+            ...........
+            X.X........
+            ...XXXXXXXX
+        ").unwrap();
+        let mut game = Game::with_seed(0).with_setup(setup);
+        game.pos = Position { x: 2, y: 17 };
+        // その場では回転できないので上にずらして回転する
+        rotate_right(&mut game);
+        assert_eq!((game.pos.x, game.pos.y), (2, 16));
+        fix_block(&mut game);
+        assert_eq!(format_field(&game.field), "\
+            .T.........\n\
+            .TT........\n\
+            XTX........\n\
+            ...XXXXXXXX\n\
+        ");
+    }
+
+    #[test]
+    fn test_tspin() {
+        let text = "
+            queue: TT
+            ..X........
+            XX...XXXXXX
+            XXX.XXXXXXX
+        ";
+        for rotated in [true, false] {
+            let mut game = Game::with_seed(0).with_setup(notation::parse(text).unwrap());
+            // 下向きにしてから、回転で差し込んだ状態にする
+            rotate_right(&mut game);
+            rotate_right(&mut game);
+            game.pos = Position { x: 3, y: 17 };
+            game.rotated = rotated;
+            game.take_events();
+            landing(&mut game).unwrap();
+            assert_eq!(game.take_events()[0], Event::Lock { lines: 2, tspin: rotated, pc: false });
+            assert_eq!(format_field(&game.field), "..X........\n");
+        }
+    }
+
+    #[test]
+    fn test_hold_rules() {
+        let setup = notation::parse("queue: TI").unwrap();
+        // 回転したままホールドする
+        let mut game = Game::with_seed(0).with_setup(setup.clone());
+        rotate_right(&mut game);
+        let rotated = game.block;
+        hold(&mut game);
+        assert_eq!(game.hold, Some(rotated));
+        // 出現時の向きに戻してホールドする
+        let mut game = Game::with_seed_and_rules(0, Ruleset { hold_reset: true, ..Ruleset::default() }).with_setup(setup.clone());
+        rotate_right(&mut game);
+        hold(&mut game);
+        assert_eq!(game.hold, Some(BLOCKS[BlockKind::T as usize]));
+        // ホールドを使えない
+        let mut game = Game::with_seed_and_rules(0, Ruleset { hold: false, ..Ruleset::default() }).with_setup(setup);
+        hold(&mut game);
+        assert_eq!((game.hold, game.holded), (None, false));
+        assert_eq!(shape_color(&game.block), block_kind::T);
+        // ネクストキューは常に`NEXT_MAX`個以上
+        assert!(game.next.len() >= NEXT_MAX);
+    }
+
+    #[test]
+    fn test_entry() {
+        let setup = notation::parse("queue: OTI").unwrap();
+        let rules = Ruleset { are: 100, line_clear_delay: 200, ..Ruleset::default() };
+        let mut game = Game::with_seed_and_rules(0, rules).with_setup(setup);
+        operate(&mut game, Operation::HardDrop);
+        landing(&mut game).unwrap();
+        assert_eq!(game.entry, Some(Entry { delay: 100, turns: 0, hold: false }));
+        // 出現待ちの間は回転とホールドだけを受け付けて出現時に適用する(IHS、IRS)
+        let pos = game.pos;
+        assert!(!operate(&mut game, Operation::HardDrop));
+        operate(&mut game, Operation::MoveLeft);
+        operate(&mut game, Operation::Hold);
+        operate(&mut game, Operation::RotateLeft);
+        operate(&mut game, Operation::Rotate180);
+        assert_eq!((game.pos.x, game.pos.y), (pos.x, pos.y));
+        assert_eq!(game.hold, None);
+        operate(&mut game, Operation::Entry);
+        assert_eq!(game.entry, None);
+        assert_eq!(game.hold.map(|hold| shape_color(&hold)), Some(block_kind::T));
+        let mut expected = Game::with_seed(0).with_setup(notation::parse("queue: I").unwrap());
+        rotate_right(&mut expected);
+        assert_eq!(game.block, expected.block);
+    }
+
+    #[test]
+    fn test_nes_rules() {
+        let rules = Ruleset::preset(Preset::Nes);
+        let mut game = Game::with_seed_and_rules(0, rules);
+        assert_eq!(game.next.len(), NEXT_MAX);
+        // 直前と同じブロックは出にくい(引き直した場合だけ1/7の確率で続く)
+        let mut repeats = 0;
+        for _ in 0..7000 {
+            let prev = shape_color(&game.block);
+            spawn_block(&mut game).unwrap();
+            repeats += (shape_color(&game.block) == prev) as usize;
+        }
+        assert!((100..400).contains(&repeats), "{repeats}");
+        // ハードドロップを使えない
+        let pos = game.pos;
+        assert!(!operate(&mut game, Operation::HardDrop));
+        assert_eq!((game.pos.x, game.pos.y), (pos.x, pos.y));
+        // 得点と落下速度はレベル(10ラインごと)で変わる
+        assert_eq!(rules.scoring.score(4, 0), 1200);
+        assert_eq!(rules.scoring.score(1, 25), 120);
+        assert_eq!(Ruleset::default().scoring.score(4, 25), 100);
+        assert_eq!(rules.gravity.msec(0), 800);
+        assert_eq!(rules.gravity.msec(95), 100);
+        assert_eq!(rules.gravity.msec(1000), 16);
+        assert_eq!(Ruleset::default().gravity.msec(95), 100);
+        assert_eq!(Ruleset::default().gravity.msec(15), 900);
+    }
+
+    #[test]
+    fn test_invisible() {
+        let setup = notation::parse("queue: OO").unwrap();
+        let rules = Ruleset { invisible: true, invisible_delay: 100, ..Ruleset::default() };
+        let mut game = Game::with_seed_and_rules(0, rules).with_setup(setup.clone());
+        operate(&mut game, Operation::HardDrop);
+        landing(&mut game).unwrap();
+        // 待ち時間が経過するまでは見える
+        let (x, y) = (game.pos.x + 1, FIELD_HEIGHT - 3);
+        assert_eq!(display_field(&game, &game.field)[y][x], block_kind::O);
+        assert_eq!(game.field[y][x], block_kind::O);
+        // 見えなくしてもフィールドには残る
+        operate(&mut game, Operation::Fade);
+        assert_eq!(display_field(&game, &game.field)[y][x], block_kind::NONE);
+        assert_eq!(game.field[y][x], block_kind::O);
+        // 待ち時間がなければすぐに見えなくなる
+        let rules = Ruleset { invisible_delay: 0, ..rules };
+        let mut game = Game::with_seed_and_rules(0, rules).with_setup(setup);
+        operate(&mut game, Operation::HardDrop);
+        landing(&mut game).unwrap();
+        assert_eq!(display_field(&game, &game.field)[y][x], block_kind::NONE);
+    }
+
+    #[test]
+    fn test_big() {
+        let rules = Ruleset { big: true, ..Ruleset::default() };
+        let mut game = Game::with_seed_and_rules(0, rules).with_setup(notation::parse("queue: OT").unwrap());
+        assert_eq!(game.field, BIG_FIELD);
+        assert_eq!((game.pos.x, game.pos.y), (3, 0));
+        // 幅5のフィールドの最下段を埋めると消える(壁だけの段は消えない)
+        game.field[BIG_HEIGHT-1][2..5].fill(block_kind::GARBAGE);
+        operate(&mut game, Operation::MoveRight);
+        operate(&mut game, Operation::HardDrop);
+        landing(&mut game).unwrap();
+        assert_eq!(game.line, 1);
+        assert_eq!(game.field[BIG_HEIGHT-1][2..7], [0, 0, 0, block_kind::O, block_kind::O]);
+        assert_eq!(game.field[BIG_HEIGHT..], BIG_FIELD[BIG_HEIGHT..]);
+        // 1マスを2×2に広げて描画する
+        let display = display_field(&game, &game.field);
+        for (y, x) in [(18, 8), (18, 9), (19, 10), (19, 11)] {
+            assert_eq!(display[y][x], block_kind::O);
+        }
+        assert_eq!(display[19][7], block_kind::NONE);
+        assert_eq!(display[19][12], W);
+        assert_eq!(display[20], EMPTY_FIELD[20]);
+    }
 }
@@ -0,0 +1,239 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use crate::game::{Preset, Ruleset};
+use crate::save::data_dir;
+
+// ハイスコアファイルのバージョン
+// 形式を変更した場合は数値を上げる
+pub const HIGHSCORE_VERSION: u32 = 1;
+
+// モードごとに保持する記録の数
+pub const MAX_ENTRIES: usize = 10;
+
+// ハイスコアの記録
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub date: String,      // 記録した日時(UTC)
+    pub name: String,      // プレイヤー名
+    pub score: usize,
+    pub time: u64,         // プレイ時間(秒)
+    pub lines: usize,
+    pub settings: String,  // 記録したフロントエンドとルール(JSON)
+}
+
+// モードごとのハイスコア
+// 各モードの記録はスコアの高い順(同点ならプレイ時間の短い順)に並ぶ
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct HighScores {
+    version: u32,
+    modes: BTreeMap<String, Vec<Entry>>,
+}
+
+// デフォルトのハイスコアファイルの保存先
+pub fn default_path() -> PathBuf {
+    data_dir().join("highscores.json")
+}
+
+impl HighScores {
+    // ハイスコアファイルを読み込む
+    // ファイルがない場合は空の記録を返す
+    pub fn load(path: &Path) -> io::Result<HighScores> {
+        let json = match fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Ok(HighScores { version: HIGHSCORE_VERSION, ..Default::default() });
+            }
+            Err(e) => return Err(e),
+        };
+        let scores: HighScores = serde_json::from_str(&json)?;
+        if scores.version != HIGHSCORE_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported high score version {} (expected {HIGHSCORE_VERSION})", scores.version),
+            ));
+        }
+        Ok(scores)
+    }
+
+    // ハイスコアファイルに書き出す
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+
+    // モードの記録
+    pub fn entries(&self, mode: &str) -> &[Entry] {
+        self.modes.get(mode).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    // 記録を追加する
+    // 上位に入った場合は順位(0始まり)を返す
+    pub fn insert(&mut self, mode: &str, entry: Entry) -> Option<usize> {
+        let entries = self.modes.entry(mode.to_owned()).or_default();
+        // 同じ記録なら先に記録したものを上位とする
+        let rank = entries
+            .iter()
+            .position(|e| (entry.score, std::cmp::Reverse(entry.time)) > (e.score, std::cmp::Reverse(e.time)))
+            .unwrap_or(entries.len());
+        if rank >= MAX_ENTRIES {
+            return None;
+        }
+        entries.insert(rank, entry);
+        entries.truncate(MAX_ENTRIES);
+        Some(rank)
+    }
+}
+
+// ハイスコアの記録結果
+pub struct Ranking {
+    pub mode: String,
+    pub rank: Option<usize>,  // 上位に入った場合の順位(0始まり)
+    pub entries: Vec<Entry>,  // 記録後のモードの記録
+}
+
+impl Ranking {
+    // 自己ベストを更新したか
+    pub fn is_personal_best(&self) -> bool {
+        self.rank == Some(0)
+    }
+}
+
+// ハイスコアファイルに記録を追加する
+pub fn record(path: &Path, mode: &str, entry: Entry) -> io::Result<Ranking> {
+    let mut scores = HighScores::load(path)?;
+    let rank = scores.insert(mode, entry);
+    if rank.is_some() {
+        scores.save(path)?;
+    }
+    Ok(Ranking { mode: mode.to_owned(), rank, entries: scores.entries(mode).to_vec() })
+}
+
+// ルールごとの記録の名前
+// 一番近いプリセットの名前と、プリセットから変更した項目を並べる(現代的なルールそのままならモード名のみ)
+// 例: `normal`、`normal, nes`、`normal, nes, hold=true`
+pub fn mode_name(mode: &str, rules: &Ruleset) -> String {
+    // ルールを書き出せない場合はパニックせずにモード名のみを返す
+    let fields = |rules: &Ruleset| match serde_json::to_value(rules) {
+        Ok(Value::Object(fields)) => Some(fields),
+        _ => None,
+    };
+    let Some(changed) = fields(rules) else {
+        return mode.to_owned();
+    };
+    let (preset, overrides) = [Preset::Guideline, Preset::Nes]
+        .into_iter()
+        .map(|preset| {
+            let overrides = fields(&Ruleset::preset(preset))
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|(key, value)| changed[key] != *value)
+                .map(|(key, _)| match &changed[&key] {
+                    Value::String(value) => format!("{key}={value}"),
+                    value => format!("{key}={value}"),
+                })
+                .collect::<Vec<_>>();
+            (preset, overrides)
+        })
+        .min_by_key(|(_, overrides)| overrides.len())
+        .unwrap();
+    if preset == Preset::Guideline && overrides.is_empty() {
+        return mode.to_owned();
+    }
+    let preset = match preset {
+        Preset::Guideline => "guideline",
+        Preset::Nes       => "nes",
+    };
+    [mode, preset].into_iter().map(str::to_owned).chain(overrides).collect::<Vec<_>>().join(", ")
+}
+
+// 記録時の設定
+// フロントエンドの名前の後にルールをJSONで書く
+pub fn settings(front_end: &str, rules: &Ruleset) -> String {
+    format!("{front_end} {}", serde_json::to_string(rules).unwrap_or_default())
+}
+
+// プレイヤー名
+// 環境変数`USER`(Windowsでは`USERNAME`)を使う
+pub fn player_name() -> String {
+    env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .unwrap_or_else(|_| "player".to_owned())
+}
+
+// 現在の日時(UTC)
+// `YYYY-MM-DD hh:mm`形式
+pub fn now() -> String {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    // 1970-01-01からの日数を年月日に変換する
+    let days = (secs / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    format!("{year:04}-{month:02}-{day:02} {:02}:{:02}", secs / 3600 % 24, secs / 60 % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(score: usize, time: u64) -> Entry {
+        Entry {
+            date: now(),
+            name: "test".to_owned(),
+            score,
+            time,
+            lines: 0,
+            settings: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_record() {
+        let path = env::temp_dir().join(format!("tetrust-highscore-test-{}.json", std::process::id()));
+        fs::remove_file(&path).ok();
+        let ranking = record(&path, "normal", entry(10, 60)).unwrap();
+        assert!(ranking.is_personal_best());
+        let ranking = record(&path, "normal", entry(5, 30)).unwrap();
+        assert_eq!(ranking.rank, Some(1));
+        // 同点ならプレイ時間の短い方が上位
+        let ranking = record(&path, "normal", entry(10, 30)).unwrap();
+        assert!(ranking.is_personal_best());
+        assert_eq!(ranking.entries.iter().map(|e| e.time).collect::<Vec<_>>(), [30, 60, 30]);
+        // 上位に入らなければ記録しない
+        for _ in 0..MAX_ENTRIES {
+            record(&path, "normal", entry(100, 0)).unwrap();
+        }
+        let ranking = record(&path, "normal", entry(1, 0)).unwrap();
+        assert_eq!(ranking.rank, None);
+        assert_eq!(HighScores::load(&path).unwrap().entries("normal").len(), MAX_ENTRIES);
+        assert!(HighScores::load(&path).unwrap().entries("puzzle").is_empty());
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_mode_name() {
+        assert_eq!(mode_name("normal", &Ruleset::default()), "normal");
+        let nes = Ruleset::preset(Preset::Nes);
+        assert_eq!(mode_name("normal", &nes), "normal, nes");
+        assert_eq!(mode_name("normal", &Ruleset { hold: true, ..nes }), "normal, nes, hold=true");
+        let rules = Ruleset { next: 0, big: true, ..Ruleset::default() };
+        assert_eq!(mode_name("normal", &rules), "normal, guideline, big=true, next=0");
+        let rules = Ruleset { gravity: crate::game::Gravity::Nes, ..Ruleset::default() };
+        assert_eq!(mode_name("normal", &rules), "normal, guideline, gravity=nes");
+    }
+}
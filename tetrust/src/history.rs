@@ -0,0 +1,103 @@
+use std::collections::VecDeque;
+use crate::game::Game;
+
+// 練習モードで保持する履歴の数
+pub const PRACTICE_HISTORY: usize = 100;
+
+// ブロックを置くごとの盤面の履歴
+// 各ブロックが出現した時点の状態を保持し、1手戻す/やり直すことができる
+pub struct History {
+    undo: VecDeque<Game>,  // 戻す用の状態(古い順)
+    redo: Vec<Game>,       // やり直す用の状態
+    piece: Game,           // 操作中のブロックが出現した時点の状態
+    limit: usize,          // 保持する履歴の数
+}
+
+impl History {
+    pub fn new(game: &Game, limit: usize) -> History {
+        History {
+            undo: VecDeque::new(),
+            redo: vec![],
+            piece: snapshot(game),
+            limit,
+        }
+    }
+
+    // ブロックを固定した後に呼び出す
+    // 上限を超えた古い履歴は捨てる
+    pub fn push(&mut self, game: &Game) {
+        self.undo.push_back(std::mem::replace(&mut self.piece, snapshot(game)));
+        if self.undo.len() > self.limit {
+            self.undo.pop_front();
+        }
+        self.redo.clear();
+    }
+
+    // 1つ前のブロックが出現した時点に戻す
+    // 戻せた場合は`true`を返す
+    pub fn undo(&mut self, game: &mut Game) -> bool {
+        let Some(prev) = self.undo.pop_back() else {
+            return false;
+        };
+        *game = prev.clone();
+        self.redo.push(std::mem::replace(&mut self.piece, prev));
+        true
+    }
+
+    // 戻した操作をやり直す
+    // やり直せた場合は`true`を返す
+    pub fn redo(&mut self, game: &mut Game) -> bool {
+        let Some(next) = self.redo.pop() else {
+            return false;
+        };
+        *game = next.clone();
+        self.undo.push_back(std::mem::replace(&mut self.piece, next));
+        true
+    }
+
+    // 戻せる数
+    pub fn len(&self) -> usize {
+        self.undo.len()
+    }
+}
+
+// 未処理のイベントを除いた状態
+// 戻したときに同じイベントが再度処理されないようにする
+fn snapshot(game: &Game) -> Game {
+    let mut game = game.clone();
+    game.events.clear();
+    game
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Operation, operate, landing};
+
+    #[test]
+    fn test_undo_redo() {
+        let mut game = Game::with_seed(0);
+        let mut history = History::new(&game, 2);
+        let mut fields = vec![game.field];
+        for _ in 0..3 {
+            operate(&mut game, Operation::HardDrop);
+            landing(&mut game).unwrap();
+            history.push(&game);
+            fields.push(game.field);
+        }
+        // 上限を超えた分は戻せない
+        assert_eq!(history.len(), 2);
+        assert!(history.undo(&mut game));
+        assert!(history.undo(&mut game));
+        assert!(!history.undo(&mut game));
+        assert_eq!(game.field, fields[1]);
+        assert!(history.redo(&mut game));
+        assert_eq!(game.field, fields[2]);
+        assert!(game.events.is_empty());
+        // 新たにブロックを置くとやり直せなくなる
+        operate(&mut game, Operation::HardDrop);
+        landing(&mut game).unwrap();
+        history.push(&game);
+        assert!(!history.redo(&mut game));
+    }
+}
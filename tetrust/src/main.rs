@@ -3,6 +3,21 @@ mod game;
 mod play;
 mod ai;
 mod ga;
+mod net;
+mod spectator;
+mod replay;
+mod save;
+mod fumen;
+mod notation;
+mod puzzle;
+mod history;
+mod finesse;
+mod stats;
+mod highscore;
+mod config;
+mod render;
+mod term;
+mod menu;
 
 use clap::{
     Args, Parser, Subcommand,
@@ -15,16 +30,60 @@ struct Cli {
     /// What mode to run the program in
     #[command(subcommand)]
     mode: Option<Mode>,
+    /// Read key bindings, handling and visual settings from the specified file [default: $XDG_CONFIG_HOME/tetrust/config.toml]
+    #[arg(long, value_name = "FILE", global = true)]
+    config: Option<std::path::PathBuf>,
+    /// Colour support of the terminal, `none` draws pieces as letters [default: auto, detected from NO_COLOR, COLORTERM and TERM]
+    #[arg(long, value_enum, value_name = "WHEN", global = true)]
+    color: Option<term::ColorMode>,
 }
 
 #[derive(Subcommand)]
 enum Mode {
     /// Run normal play
-    Normal,
+    Normal(Normal),
     /// Run auto play
     Auto(Auto),
     /// Learning with GeneticAlgorithm
     Learning,
+    /// Host a versus game over TCP
+    Host(Host),
+    /// Join a versus game hosted on another machine
+    Join(Join),
+    /// Play back a recorded replay
+    Replay(ReplayArgs),
+    /// Solve a puzzle or a pack of puzzles
+    Puzzle(PuzzleArgs),
+    /// Edit key bindings interactively and save them to the config file
+    Keys,
+}
+
+#[derive(Args)]
+struct Normal {
+    /// Broadcast the game state as JSON lines on the specified port
+    #[arg(long, value_name = "PORT")]
+    spectate: Option<u16>,
+    /// Record a replay of the game to the specified file
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["resume", "fumen", "board"])]
+    record: Option<std::path::PathBuf>,
+    /// Save the game on quit (`q`) [default file: $XDG_DATA_HOME/tetrust/save.json]
+    #[arg(long, value_name = "FILE")]
+    save: Option<Option<std::path::PathBuf>>,
+    /// Resume a saved game [default file: $XDG_DATA_HOME/tetrust/save.json]
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["fumen", "board"])]
+    resume: Option<Option<std::path::PathBuf>>,
+    /// Start from the first page of a fumen (v115)
+    #[arg(long, conflicts_with = "board")]
+    fumen: Option<String>,
+    /// Start from a board written in the plain-text notation
+    #[arg(long, value_name = "FILE")]
+    board: Option<std::path::PathBuf>,
+    /// Practice with undo and redo (keys.undo/keys.redo, `u`/`r` by default) of piece placements; the score is not ranked
+    #[arg(long, conflicts_with_all = ["record", "save"])]
+    practice: bool,
+    /// Print the board as a fumen and in the plain-text notation on quit
+    #[arg(long)]
+    export: bool,
 }
 
 #[derive(Args)]
@@ -32,16 +91,123 @@ struct Auto {
     /// Specify gene sequence [default: [100,1,10,100]]
     #[arg(short, long)]
     genome: Option<String>,
+    /// Broadcast the game state as JSON lines on the specified port
+    #[arg(long, value_name = "PORT")]
+    spectate: Option<u16>,
+}
+
+#[derive(Args)]
+struct Host {
+    /// Port number to listen on
+    #[arg(short, long, default_value_t = net::DEFAULT_PORT)]
+    port: u16,
+}
+
+#[derive(Args)]
+struct Join {
+    /// Address of the host [e.g. 192.168.0.2:7878]
+    addr: String,
+}
+
+#[derive(Args)]
+struct ReplayArgs {
+    /// Replay file recorded with `normal --record`
+    file: std::path::PathBuf,
+    /// Print the replay as a fumen (one page per piece) instead of playing it back
+    #[arg(long)]
+    fumen: bool,
+}
+
+#[derive(Args)]
+struct PuzzleArgs {
+    /// Puzzle file, or a directory of puzzle files played as a pack in file name order
+    path: std::path::PathBuf,
 }
 
 fn main() {
     // コマンドライン引数の解析
     let cli = Cli::parse();
+    // 設定ファイルの読み込み
+    let saved = config::load(cli.config.as_deref()).unwrap_or_else(|e| {
+        let cmd = clap::Command::new("tetrust");
+        clap::Error::raw(ErrorKind::InvalidValue, format!("{e}\n"))
+            .with_cmd(&cmd)
+            .exit();
+    });
+    // 端末の色数(`--color`オプションを優先する)
+    let mut config = saved.clone();
+    config.visual.color = cli.color.unwrap_or(saved.visual.color).resolve();
+    let config_path = cli.config.unwrap_or_else(config::default_path);
     match cli.mode {
-        None |
-        Some(Mode::Normal) => {
+        None => {
+            // タイトルメニュー
+            let terminal = term::Terminal::enter();
+            menu::run(saved, config, &config_path, &terminal);
+        }
+        Some(Mode::Normal(args)) => {
             // 通常プレイ
-            play::normal();
+            // 途中から始めたゲームはハイスコアに記録しない
+            let fresh = args.resume.is_none() && args.fumen.is_none() && args.board.is_none();
+            // でかブロックのフィールドは大きさが違うので、盤面を指定して始めることはできない
+            if config.rules.normal.big {
+                if let Some(arg) = [(args.fumen.is_some(), "--fumen"), (args.board.is_some(), "--board")].into_iter().find_map(|(set, arg)| set.then_some(arg)) {
+                    let cmd = clap::Command::new("tetrust");
+                    clap::Error::raw(ErrorKind::InvalidValue, format!("{arg} cannot be used when rules.normal.big is set\n"))
+                        .with_cmd(&cmd)
+                        .exit();
+                }
+            }
+            let game = match (args.resume.map(|path| path.unwrap_or_else(save::default_path)), args.fumen, args.board) {
+                // 保存したゲームを再開(セーブデータは削除する)
+                (Some(path), _, _) => save::resume(&path).unwrap_or_else(|e| {
+                    let cmd = clap::Command::new("tetrust");
+                    clap::Error::raw(ErrorKind::Io, format!("failed to resume {}: {e}\n", path.display()))
+                        .with_cmd(&cmd)
+                        .exit();
+                }),
+                // テト譜の盤面から開始
+                (None, Some(fumen), _) => match fumen::decode(&fumen) {
+                    Ok(setup) => game::Game::with_rules(config.rules.normal).with_setup(setup),
+                    Err(e) => {
+                        let cmd = clap::Command::new("tetrust");
+                        let mut err = clap::Error::new(ErrorKind::InvalidValue).with_cmd(&cmd);
+                        err.insert(ContextKind::InvalidArg, ContextValue::String("--fumen".to_owned()));
+                        err.insert(ContextKind::InvalidValue, ContextValue::String(format!("{fumen} ({e})")));
+                        err.exit();
+                    }
+                },
+                // テキスト表記の盤面から開始
+                (None, None, Some(path)) => match std::fs::read_to_string(&path) {
+                    Ok(text) => match notation::parse(&text) {
+                        Ok(setup) => game::Game::with_rules(config.rules.normal).with_setup(setup),
+                        Err(e) => {
+                            let cmd = clap::Command::new("tetrust");
+                            let mut err = clap::Error::new(ErrorKind::InvalidValue).with_cmd(&cmd);
+                            err.insert(ContextKind::InvalidArg, ContextValue::String("--board".to_owned()));
+                            err.insert(ContextKind::InvalidValue, ContextValue::String(format!("{} ({e})", path.display())));
+                            err.exit();
+                        }
+                    },
+                    Err(e) => {
+                        let cmd = clap::Command::new("tetrust");
+                        clap::Error::raw(ErrorKind::Io, format!("failed to read {}: {e}\n", path.display()))
+                            .with_cmd(&cmd)
+                            .exit();
+                    }
+                },
+                (None, None, None) => game::Game::with_rules(config.rules.normal),
+            };
+            let options = play::Options {
+                spectator: spectator(args.spectate),
+                record: args.record,
+                save: args.save.map(|path| path.unwrap_or_else(save::default_path)),
+                practice: args.practice,
+                fresh,
+                export: args.export,
+                quiet: false,
+            };
+            let terminal = term::Terminal::enter();
+            play::normal(game, options, &config, &terminal);
         }
         Some(Mode::Auto(args)) => {
             // オートプレイ
@@ -61,13 +227,77 @@ fn main() {
                             err.exit();
                         })
                 }
-                None => [100,1,10,100],
+                None => ga::DEFAULT_GENOME,
             };
-            play::auto(genome);
+            let spectator = spectator(args.spectate);
+            let terminal = term::Terminal::enter();
+            play::auto(genome, spectator, &config, &terminal);
         }
         Some(Mode::Learning) => {
             // 遺伝的アルゴリズムにて学習
             ga::learning();
         }
+        Some(Mode::Host(args)) => {
+            // 対戦(ホスト)
+            println!("waiting for opponent on port {}...", args.port);
+            let connection = std::net::TcpListener::bind(("0.0.0.0", args.port))
+                .and_then(|listener| net::host(&listener, &config.rules.versus));
+            match connection {
+                Ok((stream, seed)) => play::versus(stream, seed, &config, &term::Terminal::enter()),
+                Err(e) => eprintln!("error: {e}"),
+            }
+        }
+        Some(Mode::Join(args)) => {
+            // 対戦(参加)
+            match net::join(args.addr.as_str(), &config.rules.versus) {
+                Ok((stream, seed)) => play::versus(stream, seed, &config, &term::Terminal::enter()),
+                Err(e) => eprintln!("error: {e}"),
+            }
+        }
+        Some(Mode::Replay(args)) => {
+            // リプレイ再生
+            match replay::load(&args.file) {
+                Ok(replay) if args.fumen => {
+                    // ブロックを固定するごとに1ページとしてテト譜に変換
+                    let mut player = replay::Player::new(replay);
+                    let mut fields = vec![player.game.field];
+                    while !player.is_finished() {
+                        player.step_piece();
+                        fields.push(player.game.field);
+                    }
+                    println!("{}", fumen::encode(&fields, ""));
+                }
+                Ok(replay) => play::replay(replay, &config, &term::Terminal::enter()),
+                Err(e) => eprintln!("error: {}: {e}", args.file.display()),
+            }
+        }
+        Some(Mode::Puzzle(args)) => {
+            // パズル
+            match puzzle::load_pack(&args.path) {
+                Ok(pack) => play::puzzle(pack, &config, &term::Terminal::enter()),
+                Err(e) => eprintln!("error: {e}"),
+            }
+        }
+        Some(Mode::Keys) => {
+            // キー割り当ての変更(設定ファイルに書き戻すため、判定した色数ではなく読み込んだ設定を使う)
+            let terminal = term::Terminal::enter();
+            if play::rebind(saved, &config_path, &terminal).is_some() {
+                terminal.println(&format!("key bindings saved to {}", config_path.display()));
+            }
+        }
+    }
+}
+
+// 観戦配信サーバを起動する
+fn spectator(port: Option<u16>) -> Option<spectator::Spectator> {
+    let port = port?;
+    match spectator::Spectator::listen(port) {
+        Ok(spectator) => Some(spectator),
+        Err(e) => {
+            let cmd = clap::Command::new("tetrust");
+            clap::Error::raw(ErrorKind::Io, format!("failed to listen on port {port}: {e}\n"))
+                .with_cmd(&cmd)
+                .exit();
+        }
     }
 }
@@ -0,0 +1,228 @@
+use std::io;
+use std::net::TcpListener;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use getch_rs::Key;
+use crate::game::Game;
+use crate::ga::DEFAULT_GENOME;
+use crate::config::Config;
+use crate::render::Renderer;
+use crate::term::{self, Terminal};
+use crate::{net, play, puzzle, replay};
+
+// メニューの項目
+#[derive(Clone, Copy, PartialEq)]
+enum Item {
+    Normal,
+    Practice,
+    VersusAi,
+    Auto,
+    Puzzle,
+    Replay,
+    Keys,
+    HighScores,
+    Quit,
+}
+
+// メニューに表示する順番
+const ITEMS: [Item; 9] = [
+    Item::Normal,
+    Item::Practice,
+    Item::VersusAi,
+    Item::Auto,
+    Item::Puzzle,
+    Item::Replay,
+    Item::Keys,
+    Item::HighScores,
+    Item::Quit,
+];
+
+impl Item {
+    // 表示名
+    fn name(self) -> &'static str {
+        match self {
+            Item::Normal     => "Normal",
+            Item::Practice   => "Practice",
+            Item::VersusAi   => "Versus AI",
+            Item::Auto       => "Auto play",
+            Item::Puzzle     => "Puzzle",
+            Item::Replay     => "Replay",
+            Item::Keys       => "Key bindings",
+            Item::HighScores => "High scores",
+            Item::Quit       => "Quit",
+        }
+    }
+
+    // 選んでいる間に表示する説明
+    fn description(self) -> &'static str {
+        match self {
+            Item::Normal     => "endless play, ranked",
+            Item::Practice   => "undo/redo, not ranked",
+            Item::VersusAi   => "send garbage to the AI",
+            Item::Auto       => "watch the AI play",
+            Item::Puzzle     => "puzzle file or pack directory",
+            Item::Replay     => "play back a recorded replay",
+            Item::Keys       => "edit and save key bindings",
+            Item::HighScores => "best scores of normal play",
+            Item::Quit       => "exit tetrust",
+        }
+    }
+
+    // 開く前にファイルのパスを入力するか
+    fn needs_path(self) -> bool {
+        matches!(self, Item::Puzzle | Item::Replay)
+    }
+}
+
+// メニューの状態
+struct Menu {
+    selected: usize,          // 選んでいる項目(`ITEMS`の添字)
+    input: Option<String>,    // 入力中のファイルのパス
+    message: Option<String>,  // 直前に開いたモードの結果
+    renderer: Renderer,       // 画面の差分描画
+}
+
+// タイトルメニュー
+// 選んだモードを終了(ゲームオーバー後も含む)するとメニューに戻る
+// - 上下キー: 項目を選ぶ
+// - Enterキー: 選んだ項目を開く(パズルとリプレイはファイルのパスを入力してからEnter、Escで取り消し)
+// - `q`/Escキー: 終了
+pub fn run(mut saved: Config, mut config: Config, path: &Path, terminal: &Terminal) {
+    let menu = Arc::new(Mutex::new(Menu { selected: 0, input: None, message: None, renderer: Renderer::new() }));
+    while let Some((item, file)) = select(&menu, terminal) {
+        let message = match item {
+            // 終了時の統計は溜め込まずにメニューに表示する
+            Item::Normal => {
+                let options = play::Options { fresh: true, quiet: true, ..Default::default() };
+                Some(play::normal(Game::with_rules(config.rules.normal), options, &config, terminal))
+            }
+            Item::Practice => {
+                let options = play::Options { practice: true, quiet: true, ..Default::default() };
+                Some(play::normal(Game::with_rules(config.rules.normal), options, &config, terminal))
+            }
+            Item::VersusAi => versus_ai(&config, terminal).err().map(|e| format!("error: {e}")),
+            Item::Auto => {
+                play::auto(DEFAULT_GENOME, None, &config, terminal);
+                None
+            }
+            Item::Puzzle => match puzzle::load_pack(Path::new(&file)) {
+                Ok(pack) => {
+                    play::puzzle(pack, &config, terminal);
+                    None
+                }
+                Err(e) => Some(format!("error: {e}")),
+            },
+            Item::Replay => match replay::load(Path::new(&file)) {
+                Ok(replay) => {
+                    play::replay(replay, &config, terminal);
+                    None
+                }
+                Err(e) => Some(format!("error: {file}: {e}")),
+            },
+            Item::Keys => play::rebind(saved.clone(), path, terminal).map(|new| {
+                // 判定した色数は保ったまま新しいキー割り当てを使う
+                let color = config.visual.color;
+                saved = new;
+                config = saved.clone();
+                config.visual.color = color;
+                format!("key bindings saved to {}", path.display())
+            }),
+            Item::HighScores => {
+                play::highscores(&config.rules.normal, terminal);
+                None
+            }
+            Item::Quit => break,
+        };
+        let mut menu = menu.lock().unwrap();
+        menu.message = message;
+        // 他の画面が描画した後なのですべて描き直す
+        menu.renderer = Renderer::new();
+    }
+}
+
+// 項目を選ぶ
+// 終了する場合は`None`を返す
+fn select(menu: &Arc<Mutex<Menu>>, terminal: &Terminal) -> Option<(Item, String)> {
+    draw_menu(&mut menu.lock().unwrap());
+    // 端末の大きさが変わったら描き直す
+    let _resize = {
+        let menu = Arc::clone(menu);
+        term::on_resize(move || draw_menu(&mut menu.lock().unwrap()))
+    };
+
+    // キー入力処理
+    loop {
+        // キー入力待ち
+        let Ok(key) = terminal.getch() else {
+            continue;
+        };
+        let mut menu = menu.lock().unwrap();
+        let item = ITEMS[menu.selected];
+        if let Some(input) = &mut menu.input {
+            // ファイルのパスを入力中
+            match key {
+                Key::Char('\r') => {
+                    let file = menu.input.take().unwrap_or_default();
+                    return Some((item, file));
+                }
+                Key::Char(c) => input.push(c),
+                Key::Backspace | Key::Delete => {
+                    input.pop();
+                }
+                Key::Esc => menu.input = None,
+                _ => continue,  // 何もしない
+            }
+            draw_menu(&mut menu);
+            continue;
+        }
+        menu.message = None;
+        match key {
+            Key::Up => {
+                menu.selected = menu.selected.checked_sub(1).unwrap_or(ITEMS.len() - 1);
+            }
+            Key::Down => {
+                menu.selected = (menu.selected + 1) % ITEMS.len();
+            }
+            Key::Char('\r') if item.needs_path() => {
+                menu.input = Some(String::new());
+            }
+            Key::Char('\r') => {
+                return Some((item, String::new()));
+            }
+            Key::Char('q') | Key::Esc => {
+                return None;
+            }
+            _ => continue,  // 何もしない
+        }
+        draw_menu(&mut menu);
+    }
+}
+
+// メニューを描画する
+fn draw_menu(menu: &mut Menu) {
+    let renderer = &mut menu.renderer;
+    renderer.put_text(3, 2, "T E T R U S T");
+    for (i, item) in ITEMS.iter().enumerate() {
+        let cursor = if i == menu.selected { ">" } else { " " };
+        renderer.put_text(3, i*2+5, &format!("{cursor} {}", item.name()));
+    }
+    let item = ITEMS[menu.selected];
+    if let Some(input) = &menu.input {
+        renderer.put_text(1, 24, &format!("{} path: {input}_", item.name().to_lowercase()));
+        renderer.put_text(1, 25, "[enter] open  [esc] cancel");
+    } else {
+        renderer.put_text(1, 24, menu.message.as_deref().unwrap_or(item.description()));
+        renderer.put_text(1, 25, "[up/down] select  [enter] open  [q] quit");
+    }
+    renderer.flush();
+}
+
+// AIとの対戦
+// 自分自身をホストとしてAIの対戦相手を接続させ、ネットワーク対戦と同じ処理で対戦する
+fn versus_ai(config: &Config, terminal: &Terminal) -> io::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", 0))?;
+    play::ai_opponent(listener.local_addr()?, DEFAULT_GENOME, config.rules.versus)?;
+    let (stream, seed) = net::host(&listener, &config.rules.versus)?;
+    play::versus(stream, seed, config, terminal);
+    Ok(())
+}
@@ -0,0 +1,215 @@
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use crate::game::{Field, Ruleset, EMPTY_FIELD, FIELD_WIDTH, FIELD_HEIGHT};
+
+// 通信プロトコルのバージョン
+// フレームの形式を変更した場合は数値を上げる
+pub const PROTOCOL_VERSION: u8 = 2;
+
+// 対戦で使用するデフォルトのポート番号
+pub const DEFAULT_PORT: u16 = 7878;
+
+// フレームの種類
+const TAG_HELLO:    u8 = 0;
+const TAG_BOARD:    u8 = 1;
+const TAG_GARBAGE:  u8 = 2;
+const TAG_GAMEOVER: u8 = 3;
+
+// 対戦相手とやり取りするメッセージ
+#[derive(Debug, PartialEq)]
+pub enum Message {
+    // 接続時の挨拶(プロトコルのバージョンと共有するシード値、対戦のルールのダイジェスト)
+    Hello { version: u8, seed: u64, rules: u64 },
+    // ブロック固定後のフィールドとスコア
+    Board { field: Box<Field>, score: usize },
+    // 相手に送るおじゃまライン(段数と穴の列)
+    Garbage { lines: u8, hole: u8 },
+    // ゲームオーバー
+    GameOver,
+}
+
+// メッセージをフレームに変換する
+// フレームは「長さ(2byte, ビッグエンディアン)＋種類(1byte)＋本体」
+fn encode(msg: &Message) -> Vec<u8> {
+    let mut body = vec![];
+    match msg {
+        Message::Hello { version, seed, rules } => {
+            body.push(TAG_HELLO);
+            body.push(*version);
+            body.extend_from_slice(&seed.to_be_bytes());
+            body.extend_from_slice(&rules.to_be_bytes());
+        }
+        Message::Board { field, score } => {
+            body.push(TAG_BOARD);
+            body.extend_from_slice(&(*score as u32).to_be_bytes());
+            // 壁と番兵を除いた部分のみ送る
+            for row in &field[..FIELD_HEIGHT-2] {
+                body.extend(row[2..FIELD_WIDTH-2].iter().map(|&b| b as u8));
+            }
+        }
+        Message::Garbage { lines, hole } => {
+            body.push(TAG_GARBAGE);
+            body.push(*lines);
+            body.push(*hole);
+        }
+        Message::GameOver => {
+            body.push(TAG_GAMEOVER);
+        }
+    }
+    let mut frame = (body.len() as u16).to_be_bytes().to_vec();
+    frame.append(&mut body);
+    frame
+}
+
+// フレームの本体をメッセージに変換する
+fn decode(body: &[u8]) -> io::Result<Message> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid frame");
+    let (&tag, payload) = body.split_first().ok_or_else(invalid)?;
+    match (tag, payload.len()) {
+        (TAG_HELLO, 17) => Ok(Message::Hello {
+            version: payload[0],
+            seed: u64::from_be_bytes(payload[1..9].try_into().unwrap()),
+            rules: u64::from_be_bytes(payload[9..17].try_into().unwrap()),
+        }),
+        (TAG_BOARD, len) if len == 4 + (FIELD_HEIGHT-2) * (FIELD_WIDTH-4) => {
+            let score = u32::from_be_bytes(payload[..4].try_into().unwrap()) as usize;
+            let mut field = Box::new(EMPTY_FIELD);
+            for (row, cells) in field.iter_mut().zip(payload[4..].chunks(FIELD_WIDTH-4)) {
+                for (cell, &b) in row[2..FIELD_WIDTH-2].iter_mut().zip(cells) {
+                    *cell = b as usize;
+                }
+            }
+            Ok(Message::Board { field, score })
+        }
+        (TAG_GARBAGE, 2) => Ok(Message::Garbage {
+            lines: payload[0],
+            hole: payload[1],
+        }),
+        (TAG_GAMEOVER, 0) => Ok(Message::GameOver),
+        _ => Err(invalid()),
+    }
+}
+
+// メッセージを送信する
+pub fn send(stream: &mut impl Write, msg: &Message) -> io::Result<()> {
+    stream.write_all(&encode(msg))?;
+    stream.flush()
+}
+
+// メッセージを1つ受信する
+pub fn recv(stream: &mut impl Read) -> io::Result<Message> {
+    let mut len = [0; 2];
+    stream.read_exact(&mut len)?;
+    let mut body = vec![0; u16::from_be_bytes(len) as usize];
+    stream.read_exact(&mut body)?;
+    decode(&body)
+}
+
+// ルールのダイジェスト(FNV-1a)
+// ブロックの生成方式などが違うと同じシード値でも出現順が変わるため、接続時に照合する
+pub fn rules_digest(rules: &Ruleset) -> u64 {
+    serde_json::to_string(rules)
+        .unwrap_or_default()
+        .bytes()
+        .fold(0xcbf29ce484222325, |hash, b| (hash ^ b as u64).wrapping_mul(0x100000001b3))
+}
+
+// ルールが異なる場合のエラー
+fn rules_mismatch() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "rules mismatch (rules.versus must be the same on both sides)")
+}
+
+// ホストとして対戦相手の接続を待ち受ける
+// 接続後にシード値を決めて相手と共有する
+pub fn host(listener: &TcpListener, rules: &Ruleset) -> io::Result<(TcpStream, u64)> {
+    let (mut stream, _) = listener.accept()?;
+    stream.set_nodelay(true)?;
+    let seed = rand::random();
+    let digest = rules_digest(rules);
+    send(&mut stream, &Message::Hello { version: PROTOCOL_VERSION, seed, rules: digest })?;
+    // 相手から同じシード値とルールが返ってくれば準備完了
+    match recv(&mut stream)? {
+        Message::Hello { version: PROTOCOL_VERSION, seed: s, rules } if s == seed => {
+            if rules != digest {
+                return Err(rules_mismatch());
+            }
+            Ok((stream, seed))
+        }
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData, "handshake failed")),
+    }
+}
+
+// ホストに接続してシード値を受け取る
+pub fn join(addr: impl ToSocketAddrs, rules: &Ruleset) -> io::Result<(TcpStream, u64)> {
+    handshake(TcpStream::connect(addr)?, rules)
+}
+
+// 接続済みのホストからシード値を受け取る
+// ホストが接続を受け付けるまで待つ
+pub fn handshake(mut stream: TcpStream, rules: &Ruleset) -> io::Result<(TcpStream, u64)> {
+    stream.set_nodelay(true)?;
+    match recv(&mut stream)? {
+        Message::Hello { version: PROTOCOL_VERSION, seed, rules: host_rules } => {
+            // 受け取ったシード値と自分のルールを返して応答する(ルールが違えばホストも接続を断る)
+            let digest = rules_digest(rules);
+            send(&mut stream, &Message::Hello { version: PROTOCOL_VERSION, seed, rules: digest })?;
+            if host_rules != digest {
+                return Err(rules_mismatch());
+            }
+            Ok((stream, seed))
+        }
+        Message::Hello { version, .. } => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("protocol version mismatch (host: {version}, self: {PROTOCOL_VERSION})"),
+        )),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData, "handshake failed")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use crate::block::block_kind;
+    use crate::game::Preset;
+
+    #[test]
+    fn test_handshake_and_messages() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let guest = thread::spawn(move || {
+            let (mut stream, seed) = join(addr, &Ruleset::default()).unwrap();
+            let msg = recv(&mut stream).unwrap();
+            send(&mut stream, &msg).unwrap();
+            seed
+        });
+        let (mut stream, seed) = host(&listener, &Ruleset::default()).unwrap();
+
+        let mut field = EMPTY_FIELD;
+        field[19][2] = block_kind::T;
+        field[19][12] = block_kind::GARBAGE;
+        let msg = Message::Board { field: Box::new(field), score: 125 };
+        send(&mut stream, &msg).unwrap();
+        assert_eq!(recv(&mut stream).unwrap(), msg);
+        assert_eq!(guest.join().unwrap(), seed);
+    }
+
+    #[test]
+    fn test_rules_mismatch() {
+        // ルールが違えば両方とも接続に失敗する
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let guest = thread::spawn(move || join(addr, &Ruleset::preset(Preset::Nes)).is_err());
+        assert!(host(&listener, &Ruleset::default()).is_err());
+        assert!(guest.join().unwrap());
+        assert_ne!(rules_digest(&Ruleset::default()), rules_digest(&Ruleset::preset(Preset::Nes)));
+    }
+
+    #[test]
+    fn test_decode_invalid_frame() {
+        assert!(decode(&[]).is_err());
+        assert!(decode(&[TAG_GARBAGE, 1]).is_err());
+        assert!(decode(&[0xff]).is_err());
+        assert_eq!(decode(&[TAG_GARBAGE, 2, 5]).unwrap(), Message::Garbage { lines: 2, hole: 5 });
+    }
+}
@@ -0,0 +1,136 @@
+use crate::block::{block_kind, block_char, char_shape, shape_color, BlockColor};
+use crate::game::{Game, Setup, Field, EMPTY_FIELD, FIELD_WIDTH, FIELD_HEIGHT};
+
+// 盤面のテキスト表記
+//
+// ```text
+// # `#`から始まる行はコメント
+// hold: T
+// queue: IOSZ
+// ...........
+// X.XXXXXXXXX
+// ```
+//
+// - `hold:`/`queue:`は省略可能
+// - フィールドは1行1段、1文字1マスで、`.`は何もなし、`X`はおじゃまブロック、`IOSZJLT`は各ブロック
+// - 段数が足りない場合は下詰めで配置する(上の段は何もなし)
+
+// 壁を除いたフィールドの幅と高さ
+const WIDTH:  usize = FIELD_WIDTH - 4;
+const HEIGHT: usize = FIELD_HEIGHT - 2;
+
+// 文字に対応するブロックの種類
+fn char_color(c: char) -> Option<BlockColor> {
+    match c {
+        '.' => Some(block_kind::NONE),
+        'X' => Some(block_kind::GARBAGE),
+        c => char_shape(c).filter(|_| c.is_ascii_uppercase()).map(|shape| shape_color(&shape)),
+    }
+}
+
+// テキスト表記を読み込む
+pub fn parse(s: &str) -> Result<Setup, String> {
+    let mut hold = None;
+    let mut queue = vec![];
+    let mut rows = vec![];
+    for (i, line) in s.lines().enumerate() {
+        let line = line.trim();
+        let error = |msg: &str| format!("line {}: {msg}: {line}", i + 1);
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(value) = line.strip_prefix("hold:") {
+            hold = match value.trim() {
+                "" => None,
+                value => Some(value.chars().next().and_then(char_shape).ok_or_else(|| error("invalid hold"))?),
+            };
+        } else if let Some(value) = line.strip_prefix("queue:") {
+            queue = value
+                .trim()
+                .chars()
+                .map(|c| char_shape(c).ok_or_else(|| error("invalid queue")))
+                .collect::<Result<_, _>>()?;
+        } else {
+            let row = line
+                .chars()
+                .map(|c| char_color(c).ok_or_else(|| error("invalid cell")))
+                .collect::<Result<Vec<_>, _>>()?;
+            if row.len() != WIDTH {
+                return Err(error(&format!("row must be {WIDTH} cells wide")));
+            }
+            rows.push(row);
+        }
+    }
+    if rows.len() > HEIGHT {
+        return Err(format!("field must be at most {HEIGHT} rows high"));
+    }
+    // 下詰めで配置する
+    let mut field = EMPTY_FIELD;
+    for (field_row, row) in field[HEIGHT-rows.len()..HEIGHT].iter_mut().zip(rows) {
+        field_row[2..FIELD_WIDTH-2].copy_from_slice(&row);
+    }
+    Ok(Setup { field, hold, queue })
+}
+
+// フィールドをテキスト表記に変換する
+// 何もない上の段は省略する
+pub fn format_field(field: &Field) -> String {
+    field[..HEIGHT]
+        .iter()
+        .map(|row| &row[2..FIELD_WIDTH-2])
+        .skip_while(|row| row.iter().all(|&b| b == block_kind::NONE))
+        .map(|row| row.iter().map(|&b| block_char(b)).collect::<String>() + "\n")
+        .collect()
+}
+
+// ゲームの盤面(ホールド、ネクスト、フィールド)をテキスト表記に変換する
+pub fn format_game(game: &Game) -> String {
+    let hold = game.hold.map(|hold| block_char(shape_color(&hold)).to_string()).unwrap_or_default();
+    let queue = std::iter::once(&game.block)
+        .chain(&game.next)
+        .map(|block| block_char(shape_color(block)))
+        .collect::<String>();
+    format!("hold: {hold}\nqueue: {queue}\n{}", format_field(&game.field))
+}
+
+// テスト用にテキスト表記からフィールドを生成する
+#[cfg(test)]
+pub fn field(s: &str) -> Field {
+    parse(s).unwrap().field
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::{BLOCKS, BlockKind};
+
+    #[test]
+    fn test_parse_and_format() {
+        let text = "\
+            # T-spin double
+            hold: T
+            queue: IOS
+            .........XX
+            X...XXXXXXX
+            XX.XXXXXXXX
+        ";
+        let setup = parse(text).unwrap();
+        assert_eq!(setup.hold, Some(BLOCKS[BlockKind::T as usize]));
+        assert_eq!(setup.queue, [BlockKind::I, BlockKind::O, BlockKind::S].map(|k| BLOCKS[k as usize]));
+        assert_eq!(setup.field[19][2], block_kind::GARBAGE);
+        assert_eq!(setup.field[19][4], block_kind::NONE);
+        assert_eq!(setup.field[16], EMPTY_FIELD[16]);
+        assert_eq!(format_field(&setup.field), ".........XX\nX...XXXXXXX\nXX.XXXXXXXX\n");
+
+        let game = Game::with_seed(0).with_setup(setup);
+        assert!(format_game(&game).starts_with("hold: T\nqueue: IOS"));
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(parse("..........").is_err());
+        assert!(parse("..........a").is_err());
+        assert!(parse("queue: IOP").is_err());
+        assert!(parse(&"...........\n".repeat(HEIGHT + 1)).is_err());
+    }
+}
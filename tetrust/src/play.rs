@@ -1,158 +1,1313 @@
+use std::collections::VecDeque;
+use std::io;
+use std::net::{SocketAddr, TcpStream};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
 use std::{thread, time};
-use getch_rs::{Getch, Key};
+use getch_rs::Key;
 use crate::game::*;
 use crate::ai::eval;
 use crate::ga::GenoSeq;
+use crate::net::{self, Message};
+use crate::spectator::Spectator;
+use crate::replay::{Recorder, Settings, Replay, Player};
+use crate::save;
+use crate::fumen;
+use crate::notation;
+use crate::puzzle::{Puzzle, Attempt, State};
+use crate::history::{History, PRACTICE_HISTORY};
+use crate::finesse::Finesse;
+use crate::stats::Stats;
+use crate::highscore::{self, Entry, Ranking};
+use crate::config::{self, Config, Action, KeyName, Bindings, ACTIONS};
+use crate::render::Renderer;
+use crate::term::{self, Terminal};
+
+// 一時停止の状態
+#[derive(Clone, Copy, PartialEq)]
+enum Pause {
+    Running,            // プレイ中
+    Paused,             // 一時停止中(盤面は隠す)
+    Resuming(Instant),  // 再開までのカウントダウン中(カウントダウンを始めた時刻)
+}
+
+// 一時停止から再開するまでのカウントダウン(秒)
+const RESUME_COUNTDOWN: u64 = 3;
+
+// 通常プレイの状態
+struct Play {
+    game: Game,
+    recorder: Option<Recorder>,  // リプレイの記録
+    history: Option<History>,    // 練習モードの履歴
+    finesse: Finesse,            // フィネスの判定
+    stats: Stats,                // 統計
+    ranked: bool,                // スコアを記録の対象とするか(練習モードと途中から始めたゲームでは対象外)
+    ranking: Option<Result<Ranking, String>>,  // ゲームオーバー時のハイスコアの記録結果
+    over: bool,                  // ゲームオーバー済みか
+    grounded: bool,              // 固定猶予(lock delay)の間、接地して固定を待っているか
+    entering: Option<Instant>,   // 出現待ち(ARE)を始めた時刻
+    fading: usize,               // 見えなくなるまでの時間を数え始めたブロックの数(インビジブル)
+    fade_epoch: Instant,         // 見えなくなるまでの時間を数え直した時刻(やり直しや1手戻すで変わる)
+    pause: Pause,                // 一時停止の状態
+    renderer: Renderer,          // 画面の差分描画
+    quit: bool,                  // 終了したか(自然落下などのスレッドを止める)
+}
+
+impl Play {
+    // 操作を適用する
+    // ブロックを生成できずゲームオーバーになった場合は`Err(())`を返す
+    fn operate(&mut self, op: Operation) -> Result<(), ()> {
+        if self.over {
+            return Err(());
+        }
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(op);
+        }
+        self.stats.input(op);
+        if !operate(&mut self.game, op) {
+            if op == Operation::Entry {
+                // 出現待ちの間の回転とホールドを適用したブロックから数え直す
+                self.entering = None;
+                self.finesse.reset(&self.game);
+            } else {
+                self.finesse.record(op, &self.game);
+            }
+            return Ok(());
+        }
+        self.finesse.lock(&self.game);
+        let result = landing(&mut self.game);
+        self.grounded = false;
+        self.finesse.reset(&self.game);
+        if let Some(history) = &mut self.history {
+            history.push(&self.game);
+        }
+        if result.is_err() {
+            self.over = true;
+            if self.ranked {
+                self.ranking = Some(self.record_highscore());
+            }
+        }
+        result
+    }
+
+    // 自然落下させる
+    // 固定猶予が設定されている場合は、接地を検出してから猶予の間は固定しない
+    fn gravity(&mut self, lock_delay: Option<u64>) -> Result<(), ()> {
+        if self.game.entry.is_some() {
+            // 出現待ちの間は落下しない
+            return Ok(());
+        }
+        let below = Position { x: self.game.pos.x, y: self.game.pos.y + 1 };
+        let landed = is_collision(&self.game.field, &below, &self.game.block);
+        if lock_delay.is_some() && landed && !self.grounded {
+            self.grounded = true;
+            return Ok(());
+        }
+        self.grounded = false;
+        self.operate(Operation::Gravity)
+    }
+
+    // 次の自然落下までの間隔(ミリ秒)
+    fn gravity_msec(&self, lock_delay: Option<u64>) -> u64 {
+        match lock_delay {
+            Some(lock_delay) if self.grounded => lock_delay,
+            _ => self.game.rules.gravity.msec(self.game.line),
+        }
+    }
+
+    // ハイスコアを記録する
+    fn record_highscore(&self) -> Result<Ranking, String> {
+        let entry = Entry {
+            date: highscore::now(),
+            name: highscore::player_name(),
+            score: self.game.score,
+            time: self.stats.elapsed().as_secs(),
+            lines: self.game.line,
+            settings: highscore::settings("terminal", &self.game.rules),
+        };
+        let path = highscore::default_path();
+        highscore::record(&path, &highscore::mode_name("normal", &self.game.rules), entry)
+            .map_err(|e| format!("failed to record high score to {}: {e}", path.display()))
+    }
+
+    // 発生したイベントを統計に反映して観戦者に配信する
+    fn flush(&mut self, spectator: Option<&Spectator>) {
+        let events = self.game.take_events();
+        for event in &events {
+            self.stats.record(event);
+        }
+        if let Some(spectator) = spectator {
+            if !events.is_empty() {
+                spectator.publish(&self.game, &events);
+            }
+        }
+    }
+
+    // 一時停止する
+    fn pause(&mut self) {
+        self.pause = Pause::Paused;
+        self.stats.pause();
+    }
+
+    // 再開までのカウントダウンを始める
+    fn start_resume(&mut self) -> Instant {
+        let started = Instant::now();
+        self.pause = Pause::Resuming(started);
+        started
+    }
+
+    // 一時停止から再開する
+    fn resume(&mut self) {
+        self.pause = Pause::Running;
+        self.stats.resume();
+    }
+
+    // 新しいゲームで最初からやり直す
+    // 中断したゲームはハイスコアに記録しない(やり直した後のゲームは練習モードでなければ記録する)
+    fn restart(&mut self) {
+        self.game = Game::with_rules(self.game.rules);
+        self.ranked = self.history.is_none();
+        if let Some(recorder) = &mut self.recorder {
+            recorder.restart(&self.game);
+        }
+        if self.history.is_some() {
+            self.history = Some(History::new(&self.game, PRACTICE_HISTORY));
+        }
+        self.finesse = Finesse::new(&self.game);
+        self.stats = Stats::new();
+        self.ranking = None;
+        self.over = false;
+        self.grounded = false;
+        self.entering = None;
+        self.reset_fading();
+        self.pause = Pause::Running;
+    }
+
+    // 見えなくなるまでの時間を今の盤面から数え直す
+    // 数えていた時間は捨て、まだ見えているブロックは改めて待ち時間を数える
+    fn reset_fading(&mut self) {
+        self.fading = self.game.faded;
+        self.fade_epoch = Instant::now();
+    }
+
+    // 練習モードで1つ前のブロックが出現した時点に戻す
+    fn undo(&mut self) -> bool {
+        let Some(history) = &mut self.history else {
+            return false;
+        };
+        let undone = history.undo(&mut self.game);
+        // 戻した場合は出現待ちを待たない
+        enter(&mut self.game);
+        self.entering = None;
+        self.reset_fading();
+        self.over &= !undone;
+        self.finesse.reset(&self.game);
+        undone
+    }
+
+    // 練習モードで戻した操作をやり直す
+    fn redo(&mut self) -> bool {
+        let Some(history) = &mut self.history else {
+            return false;
+        };
+        let redone = history.redo(&mut self.game);
+        enter(&mut self.game);
+        self.entering = None;
+        self.reset_fading();
+        if redone {
+            // ゲームオーバーになった手をやり直した場合は再びゲームオーバー
+            self.over = is_collision(&self.game.field, &self.game.pos, &self.game.block);
+            self.finesse.reset(&self.game);
+        }
+        redone
+    }
+}
+
+// 通常プレイのオプション
+#[derive(Default)]
+pub struct Options {
+    pub spectator: Option<Spectator>,  // 観戦配信
+    pub record: Option<PathBuf>,       // リプレイの保存先
+    pub save: Option<PathBuf>,         // 中断時にゲームを保存する場合の保存先
+    pub practice: bool,                // 練習モード(1手戻す/やり直すことができ、スコアは記録しない)
+    pub fresh: bool,                   // 最初から始めたゲームか(盤面を指定したり再開したりしたゲームはスコアを記録しない)
+    pub export: bool,                  // 終了時の盤面を表示するか
+    pub quiet: bool,                   // 終了時に統計を表示しないか(メニューから開いた場合は結果をメニューに表示する)
+}
 
 // 通常プレイ
-pub fn normal() {
-    let game = Arc::new(Mutex::new(Game::new()));
+// 一時停止キーで一時停止(もう一度押すとカウントダウンの後に再開)、やり直しキーで新しいゲームを始める
+// 練習モードでは以下の操作も可能
+// - `u`キー: 1つ前のブロックに戻す
+// - `r`キー: 戻した操作をやり直す
+// 終了時の結果を1行にまとめて返す
+pub fn normal(game: Game, Options { spectator, record, save, practice, fresh, export, quiet }: Options, config: &Config, terminal: &Terminal) -> String {
+    let recorder = record.map(|path| {
+        // DAS/ARRは端末のキーリピートに任せる
+        let settings = Settings { das: None, arr: None, gravity: game.rules.gravity.msec(0) };
+        Recorder::new(&game, settings, path)
+    });
+    let history = practice.then(|| History::new(&game, PRACTICE_HISTORY));
+    let finesse = Finesse::new(&game);
+    let play = Arc::new(Mutex::new(Play {
+        game,
+        recorder,
+        history,
+        finesse,
+        stats: Stats::new(),
+        ranked: fresh && !practice,
+        ranking: None,
+        over: false,
+        grounded: false,
+        entering: None,
+        fading: 0,
+        fade_epoch: Instant::now(),
+        pause: Pause::Running,
+        renderer: Renderer::new(),
+        quit: false,
+    }));
 
-    // 画面クリア
-    println!("\x1b[2J\x1b[H\x1b[?25l");
     // フィールドを描画
-    draw(&game.lock().unwrap());
+    draw_play(&mut play.lock().unwrap(), config);
+    // 端末の大きさが変わったら描き直す
+    let _resize = {
+        let play = Arc::clone(&play);
+        let config = config.clone();
+        term::on_resize(move || draw_play(&mut play.lock().unwrap(), &config))
+    };
 
     // 自然落下処理
     {
-        let game = Arc::clone(&game);
+        let play = Arc::clone(&play);
+        let shared = Arc::clone(&play);
+        let spectator = spectator.clone();
+        let config = config.clone();
+        let lock_delay = config.handling.lock_delay;
         let _ = thread::spawn(move || {
             loop {
                 // nミリ秒間スリーブする
-                let sleep_msec = match 1000u64.saturating_sub((game.lock().unwrap().line as u64 / 10) * 100) {
-                    0 => 100,
-                    msec => msec,
-                };
+                let sleep_msec = play.lock().unwrap().gravity_msec(lock_delay);
                 thread::sleep(time::Duration::from_millis(sleep_msec));
                 // 自然落下
-                let mut game = game.lock().unwrap();
-                let new_pos = Position {
-                    x: game.pos.x,
-                    y: game.pos.y + 1,
-                };
-                if !is_collision(&game.field, &new_pos, &game.block) {
-                    // posの座標を更新
-                    game.pos = new_pos;
-                } else {
-                    // ブロック落下後の処理
-                    if landing(&mut game).is_err() {
-                        // ブロックを生成できないならゲームオーバー
-                        gameover(&game);
-                        break;
-                    }
+                let mut play = play.lock().unwrap();
+                if play.quit {
+                    break;
                 }
+                if play.over || play.pause != Pause::Running {
+                    // ゲームオーバー後や一時停止中は落下しない(練習モードでは戻して再開できる)
+                    continue;
+                }
+                // ブロックを生成できない場合はゲームオーバーを描画する
+                play.gravity(lock_delay).ok();
+                wait_entry(&shared, &mut play, spectator.as_ref(), &config);
+                wait_fade(&shared, &mut play, spectator.as_ref(), &config);
                 // フィールドを描画
-                draw(&game);
+                play.flush(spectator.as_ref());
+                draw_play(&mut play, &config);
             }
         });
     }
 
     // キー入力処理
-    let g = Getch::new();
     loop {
         // キー入力待ち
-        match g.getch() {
-            Ok(Key::Left) => {
-                let mut game = game.lock().unwrap();
-                let new_pos = Position {
-                    x: game.pos.x.checked_sub(1).unwrap_or(game.pos.x),
-                    y: game.pos.y,
-                };
-                move_block(&mut game, new_pos);
-                draw(&game);
-            }
-            Ok(Key::Down) => {
-                let mut game = game.lock().unwrap();
-                let new_pos = Position {
-                    x: game.pos.x,
-                    y: game.pos.y + 1,
-                };
-                move_block(&mut game, new_pos);
-                draw(&game);
-            }
-            Ok(Key::Right) => {
-                let mut game = game.lock().unwrap();
-                let new_pos = Position {
-                    x: game.pos.x + 1,
-                    y: game.pos.y,
+        match terminal.getch() {
+            Ok(key) if config.keys.is(Action::Quit, &key) => {
+                break;
+            }
+            Ok(key) if config.keys.is(Action::Restart, &key) => {
+                // 新しいゲームでやり直す
+                let mut play = play.lock().unwrap();
+                play.restart();
+                draw_play(&mut play, config);
+            }
+            Ok(key) if config.keys.is(Action::Pause, &key) => {
+                let shared = Arc::clone(&play);
+                let mut play = play.lock().unwrap();
+                match play.pause {
+                    Pause::Running if !play.over => {
+                        // 一時停止
+                        play.pause();
+                    }
+                    Pause::Paused => {
+                        // 再開までのカウントダウン
+                        let started = play.start_resume();
+                        resume_countdown(shared, started, config.clone());
+                    }
+                    _ => continue,
+                }
+                draw_play(&mut play, config);
+            }
+            Ok(key) if config.keys.is(Action::Undo, &key) || config.keys.is(Action::Redo, &key) => {
+                // 練習モードの1手戻す/やり直す
+                let shared = Arc::clone(&play);
+                let mut play = play.lock().unwrap();
+                if play.pause != Pause::Running {
+                    continue;
+                }
+                let changed = if config.keys.is(Action::Undo, &key) { play.undo() } else { play.redo() };
+                if changed {
+                    wait_fade(&shared, &mut play, spectator.as_ref(), config);
+                    draw_play(&mut play, config);
+                }
+            }
+            Ok(key) => {
+                let Some(op) = config.keys.operation(&key) else {
+                    continue;
                 };
-                move_block(&mut game, new_pos);
-                draw(&game);
-            }
-            Ok(Key::Up) => {
-                // ハードドロップ
-                let mut game = game.lock().unwrap();
-                hard_drop(&mut game);
-                if landing(&mut game).is_err() {
-                    // ブロックを生成できないならゲームオーバー
-                    gameover(&game);
+                let shared = Arc::clone(&play);
+                let mut play = play.lock().unwrap();
+                if play.over || play.pause != Pause::Running {
+                    // ゲームオーバー後や一時停止中は操作を受け付けない
+                    continue;
+                }
+                // ブロックを生成できない場合はゲームオーバーを描画する
+                play.operate(op).ok();
+                wait_entry(&shared, &mut play, spectator.as_ref(), config);
+                wait_fade(&shared, &mut play, spectator.as_ref(), config);
+                play.flush(spectator.as_ref());
+                draw_play(&mut play, config);
+            }
+            _ => (),  // 何もしない
+        }
+    }
+
+    // 終了処理(結果は元の画面に戻してから表示する)
+    let mut play = play.lock().unwrap();
+    play.quit = true;
+    // `--export`では終了時の盤面をテト譜とテキスト表記(`--board`で読み込める)で表示
+    if export {
+        terminal.println(&format!("fumen: {}", fumen::encode_game(&play.game)));
+        terminal.println(notation::format_game(&play.game).trim_end());
+    }
+    // リプレイを保存
+    if let Some(recorder) = play.recorder.take() {
+        if let Err(e) = recorder.save(&play.game) {
+            terminal.eprintln(&format!("failed to save replay: {e}"));
+        }
+    }
+    // 中断したゲームを保存
+    if let Some(path) = save.filter(|_| !play.over) {
+        match save::save(&path, &play.game) {
+            Ok(()) => terminal.println(&format!("game saved to {}", path.display())),
+            Err(e) => terminal.eprintln(&format!("failed to save game: {e}")),
+        }
+    }
+    // 統計とフィネスの判定結果を表示
+    if !quiet {
+        for (label, value) in play.stats.summary() {
+            terminal.println(&format!("{label:<8}{value}"));
+        }
+        terminal.println(&format!("finesse: {} faults in {} pieces", play.finesse.faults, play.finesse.pieces));
+        if !play.ranked {
+            terminal.println(&format!("practice session: score {} is not ranked", play.game.score));
+        }
+    }
+    format!(
+        "score {}, {} lines, {} finesse faults in {} pieces{}",
+        play.game.score,
+        play.game.line,
+        play.finesse.faults,
+        play.finesse.pieces,
+        if play.ranked { "" } else { " (not ranked)" },
+    )
+}
+
+// 出現待ちが始まったら、待ち時間の経過後に次のブロックを出現させる
+// 出現待ちの間にやり直したり1手戻したりした場合は何もしない(一時停止中は再開まで待つ)
+fn wait_entry(shared: &Arc<Mutex<Play>>, play: &mut Play, spectator: Option<&Spectator>, config: &Config) {
+    let Some(entry) = play.game.entry.filter(|_| play.entering.is_none()) else {
+        return;
+    };
+    let started = Instant::now();
+    play.entering = Some(started);
+    let play = Arc::clone(shared);
+    let spectator = spectator.cloned();
+    let config = config.clone();
+    let _ = thread::spawn(move || {
+        loop {
+            thread::sleep(time::Duration::from_millis(entry.delay));
+            let mut play = play.lock().unwrap();
+            if play.quit || play.entering != Some(started) {
+                return;
+            }
+            if play.pause != Pause::Running {
+                continue;
+            }
+            play.operate(Operation::Entry).ok();
+            play.flush(spectator.as_ref());
+            draw_play(&mut play, &config);
+            return;
+        }
+    });
+}
+
+// インビジブルで固定したブロックごとに、待ち時間の経過後に見えなくする
+// 一時停止中は待ち時間を数えず、再開後に改めて待つ
+fn wait_fade(shared: &Arc<Mutex<Play>>, play: &mut Play, spectator: Option<&Spectator>, config: &Config) {
+    let delay = play.game.rules.invisible_delay;
+    if !play.game.rules.invisible || delay == 0 {
+        return;
+    }
+    while play.fading < play.game.locks {
+        play.fading += 1;
+        let epoch = play.fade_epoch;
+        let play = Arc::clone(shared);
+        let spectator = spectator.cloned();
+        let config = config.clone();
+        let _ = thread::spawn(move || {
+            loop {
+                thread::sleep(time::Duration::from_millis(delay));
+                let mut play = play.lock().unwrap();
+                if play.quit || play.fade_epoch != epoch {
+                    return;
+                }
+                if play.pause != Pause::Running {
+                    continue;
+                }
+                play.operate(Operation::Fade).ok();
+                play.flush(spectator.as_ref());
+                draw_play(&mut play, &config);
+                return;
+            }
+        });
+    }
+}
+
+// 一時停止から再開するまでカウントダウンする
+// カウントダウン中に再び一時停止したりやり直したりした場合は何もしない
+fn resume_countdown(play: Arc<Mutex<Play>>, started: Instant, config: Config) {
+    let _ = thread::spawn(move || {
+        loop {
+            thread::sleep(time::Duration::from_secs(1));
+            let mut play = play.lock().unwrap();
+            if play.quit || play.pause != Pause::Resuming(started) {
+                return;
+            }
+            if started.elapsed().as_secs() >= RESUME_COUNTDOWN {
+                play.resume();
+                draw_play(&mut play, &config);
+                return;
+            }
+            draw_play(&mut play, &config);
+        }
+    });
+}
+
+// 通常プレイの画面を描画する
+fn draw_play(play: &mut Play, config: &Config) {
+    // 一時停止中は盤面を隠す
+    let renderer = &mut play.renderer;
+    match play.pause {
+        Pause::Running => (),
+        Pause::Paused => {
+            renderer.put_text(1, 1, "PAUSED");
+            renderer.put_text(1, 3, &format!("[{}] resume", config.keys.label(Action::Pause)));
+            renderer.put_text(1, 4, &format!("[{}] restart", config.keys.label(Action::Restart)));
+            renderer.put_text(1, 5, &format!("[{}] quit", config.keys.label(Action::Quit)));
+            renderer.flush();
+            return;
+        }
+        Pause::Resuming(started) => {
+            let remaining = RESUME_COUNTDOWN.saturating_sub(started.elapsed().as_secs());
+            renderer.put_text(1, 1, &format!("RESUMING IN {remaining}"));
+            renderer.flush();
+            return;
+        }
+    }
+    draw_finesse(&play.finesse, renderer);
+    draw_stats(&play.stats, renderer);
+    match &play.history {
+        None if play.over => {
+            gameover(&play.game, config, renderer);
+            if let Some(ranking) = &play.ranking {
+                draw_ranking(ranking, 27, renderer);
+            }
+        }
+        None => draw(&play.game, &config.visual, renderer),
+        Some(history) => {
+            // 練習モードの状態を描画(戻した場合はゲームオーバーの表示を消す)
+            draw(&play.game, &config.visual, renderer);
+            if play.over {
+                renderer.put_text(1, 24, "GAMEOVER");
+            }
+            renderer.put_text(1, 25, &format!(
+                "PRACTICE (not ranked)  undo: {}  [{}] undo  [{}] redo  [{}] quit",
+                history.len(),
+                config.keys.label(Action::Undo),
+                config.keys.label(Action::Redo),
+                config.keys.label(Action::Quit),
+            ));
+        }
+    }
+    renderer.flush();
+}
+
+// フィネスの判定結果を描画する
+fn draw_finesse(finesse: &Finesse, renderer: &mut Renderer) {
+    renderer.put_text(40, 2, "FINESSE");
+    renderer.put_text(40, 3, &format!("faults {}/{}", finesse.faults, finesse.pieces));
+    if let Some((count, min)) = finesse.last {
+        renderer.put_text(40, 4, &format!("last   {count} (min {min})"));
+    }
+}
+
+// ハイスコアを`y`行目から描画する
+fn draw_ranking(ranking: &Result<Ranking, String>, y: usize, renderer: &mut Renderer) {
+    let ranking = match ranking {
+        Ok(ranking) => ranking,
+        Err(e) => {
+            renderer.put_text(1, y, e);
+            return;
+        }
+    };
+    let mut title = format!("HIGH SCORES ({})", ranking.mode);
+    if ranking.is_personal_best() {
+        title.push_str("  NEW PERSONAL BEST!");
+    }
+    renderer.put_text(1, y, &title);
+    if ranking.entries.is_empty() {
+        renderer.put_text(1, y+1, "no records yet");
+    }
+    for (i, entry) in ranking.entries.iter().enumerate() {
+        let mark = if ranking.rank == Some(i) { "<" } else { "" };
+        renderer.put_text(1, y+i+1, &format!(
+            "{:>2}. {:>6}  {:>3} lines  {:>2}:{:02}  {}  {}  {mark}",
+            i + 1,
+            entry.score,
+            entry.lines,
+            entry.time / 60,
+            entry.time % 60,
+            entry.date,
+            entry.name,
+        ));
+    }
+}
+
+// ハイスコアの一覧
+// 設定したルールの記録を表示し、何かキーを押すと戻る
+pub fn highscores(rules: &Ruleset, terminal: &Terminal) {
+    let path = highscore::default_path();
+    let mode = highscore::mode_name("normal", rules);
+    let ranking = Arc::new(highscore::HighScores::load(&path)
+        .map(|scores| Ranking { rank: None, entries: scores.entries(&mode).to_vec(), mode })
+        .map_err(|e| format!("failed to read high scores from {}: {e}", path.display())));
+    let shared = Arc::new(Mutex::new(Renderer::new()));
+    let draw_highscores = move |renderer: &mut Renderer| {
+        draw_ranking(&ranking, 2, renderer);
+        renderer.put_text(1, 25, "press any key to return");
+        renderer.flush();
+    };
+    draw_highscores(&mut shared.lock().unwrap());
+    // 端末の大きさが変わったら描き直す
+    let _resize = {
+        let shared = Arc::clone(&shared);
+        let draw_highscores = draw_highscores.clone();
+        term::on_resize(move || draw_highscores(&mut shared.lock().unwrap()))
+    };
+    terminal.getch().ok();
+}
+
+// 統計を描画する
+fn draw_stats(stats: &Stats, renderer: &mut Renderer) {
+    renderer.put_text(40, 6, "STATS");
+    for (i, (label, value)) in stats.summary().iter().enumerate() {
+        renderer.put_text(40, i+7, &format!("{label:<8}{value}"));
+    }
+}
+
+// 発生したイベントを観戦者に配信する
+fn broadcast(game: &mut Game, spectator: Option<&Spectator>) {
+    let events = game.take_events();
+    if let Some(spectator) = spectator {
+        if !events.is_empty() {
+            spectator.publish(game, &events);
+        }
+    }
+}
+
+// リプレイの再生速度
+const REPLAY_SPEEDS: [f64; 6] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+
+// リプレイ再生の状態
+struct Playback {
+    player: Player,
+    clock: f64,            // 再生位置(ミリ秒)
+    speed: usize,          // 再生速度(`REPLAY_SPEEDS`の添字)
+    paused: bool,          // 一時停止中か
+    goto: Option<String>,  // 入力中の移動先のブロック番号
+    renderer: Renderer,    // 画面の差分描画
+    quit: bool,            // 終了したか(再生処理のスレッドを止める)
+}
+
+// リプレイ再生
+// - スペースキー: 再生/一時停止
+// - `+`/`-`キー: 再生速度の変更(0.25倍～8倍)
+// - `n`キー: 次のブロックまで進める
+// - `g`キー: 番号を入力してEnterでn個目のブロックに移動
+// これらのキー(`config::REPLAY_KEYS`)は終了キーに割り当てられない
+pub fn replay(replay: Replay, config: &Config, terminal: &Terminal) {
+    let playback = Arc::new(Mutex::new(Playback {
+        player: Player::new(replay),
+        clock: 0.,
+        speed: 2,
+        paused: false,
+        goto: None,
+        renderer: Renderer::new(),
+        quit: false,
+    }));
+
+    // フィールドを描画
+    draw_playback(&mut playback.lock().unwrap(), config);
+    // 端末の大きさが変わったら描き直す
+    let _resize = {
+        let playback = Arc::clone(&playback);
+        let config = config.clone();
+        term::on_resize(move || draw_playback(&mut playback.lock().unwrap(), &config))
+    };
+
+    // 再生処理
+    {
+        let playback = Arc::clone(&playback);
+        let config = config.clone();
+        let _ = thread::spawn(move || {
+            let mut last = time::Instant::now();
+            loop {
+                thread::sleep(time::Duration::from_millis(16));
+                let elapsed = last.elapsed().as_secs_f64() * 1000.;
+                last = time::Instant::now();
+                let mut pb = playback.lock().unwrap();
+                if pb.quit {
                     break;
                 }
-                draw(&game);
+                if pb.paused || pb.player.is_finished() {
+                    continue;
+                }
+                pb.clock += elapsed * REPLAY_SPEEDS[pb.speed];
+                let clock = pb.clock as u64;
+                if pb.player.advance(clock) || pb.player.is_finished() {
+                    draw_playback(&mut pb, &config);
+                }
             }
-            Ok(Key::Char('z')) => {
-                // 左回転
-                let mut game = game.lock().unwrap();
-                rotate_left(&mut game);
-                draw(&game);
+        });
+    }
+
+    // キー入力処理
+    loop {
+        // キー入力待ち
+        let key = terminal.getch();
+        let mut pb = playback.lock().unwrap();
+        if let Some(goto) = &mut pb.goto {
+            // 移動先のブロック番号を入力中
+            match key {
+                Ok(Key::Char(c @ '0'..='9')) => goto.push(c),
+                Ok(Key::Backspace | Key::Delete) => {
+                    goto.pop();
+                }
+                Ok(Key::Char('\r')) => {
+                    if let Ok(n) = goto.parse() {
+                        pb.player.seek_piece(n);
+                        pb.clock = pb.player.time as f64;
+                    }
+                    pb.goto = None;
+                }
+                Ok(Key::Esc) => pb.goto = None,
+                _ => (),  // 何もしない
             }
-            Ok(Key::Char('x')) => {
-                // 右回転
-                let mut game = game.lock().unwrap();
-                rotate_right(&mut game);
-                draw(&game);
+            draw_playback(&mut pb, config);
+            continue;
+        }
+        match key {
+            Ok(key) if config.keys.is(Action::Quit, &key) => {
+                pb.quit = true;
+                break;
             }
             Ok(Key::Char(' ')) => {
-                // ホールド
-                let mut game = game.lock().unwrap();
-                hold(&mut game);
-                draw(&game);
+                pb.paused = !pb.paused;
             }
-            Ok(Key::Char('q')) => {
-                break;
+            Ok(Key::Char('+')) => {
+                pb.speed = (pb.speed + 1).min(REPLAY_SPEEDS.len() - 1);
             }
-            _ => (),  // 何もしない
+            Ok(Key::Char('-')) => {
+                pb.speed = pb.speed.saturating_sub(1);
+            }
+            Ok(Key::Char('n')) => {
+                pb.paused = true;
+                pb.player.step_piece();
+                pb.clock = pb.player.time as f64;
+            }
+            Ok(Key::Char('g')) => {
+                pb.paused = true;
+                pb.goto = Some(String::new());
+            }
+            _ => continue,  // 何もしない
         }
+        draw_playback(&mut pb, config);
     }
+}
 
-    // 終了処理
-    quit();
+// リプレイ再生中の画面を描画する
+fn draw_playback(pb: &mut Playback, config: &Config) {
+    let player = &pb.player;
+    let renderer = &mut pb.renderer;
+    draw(&player.game, &config.visual, renderer);
+    // 再生状態を描画
+    let state = if player.is_finished() {
+        "END"
+    } else if pb.paused {
+        "PAUSE"
+    } else {
+        "PLAY"
+    };
+    renderer.put_text(1, 24, &format!(
+        "{state} x{:.2}  piece {}/{}  {:.1}s",
+        REPLAY_SPEEDS[pb.speed],
+        player.piece,
+        player.pieces,
+        pb.clock / 1000.,
+    ));
+    let status = if let Some(goto) = &pb.goto {
+        format!("goto piece: {goto}")
+    } else if player.is_finished() {
+        // 記録した最終スコアと一致しなければ再現に失敗している
+        if player.verify() {
+            format!("replay verified (score: {})", player.game.score)
+        } else {
+            format!(
+                "DESYNC: score {} / line {} (recorded score {} / line {})",
+                player.game.score, player.game.line, player.replay.score, player.replay.line,
+            )
+        }
+    } else {
+        format!("[space] play/pause  [+/-] speed  [n] next piece  [g] goto piece  [{}] quit", config.keys.label(Action::Quit))
+    };
+    renderer.put_text(1, 25, &status);
+    renderer.flush();
+}
+
+// パズルの状態
+struct Solving {
+    pack: Vec<Puzzle>,
+    index: usize,        // 挑戦中のパズル(`pack`の添字)
+    attempt: Attempt,
+    renderer: Renderer,  // 画面の差分描画
+}
+
+// パズル
+// - 1つ戻すキー(`keys.undo`): 1つ前のブロックに戻す
+// - `r`キー: 最初からやり直す
+// - `n`/`p`キー: パック内の次/前のパズル
+// `r`/`n`/`p`キー(`config::PUZZLE_KEYS`)はパズルで受け付ける操作に割り当てられない
+// 自然落下はしない
+pub fn puzzle(pack: Vec<Puzzle>, config: &Config, terminal: &Terminal) {
+    let attempt = Attempt::new(&pack[0], config.rules.puzzle);
+    let solving = Arc::new(Mutex::new(Solving { pack, index: 0, attempt, renderer: Renderer::new() }));
+
+    // フィールドを描画
+    draw_puzzle(&mut solving.lock().unwrap(), config);
+    // 端末の大きさが変わったら描き直す
+    let _resize = {
+        let solving = Arc::clone(&solving);
+        let config = config.clone();
+        term::on_resize(move || draw_puzzle(&mut solving.lock().unwrap(), &config))
+    };
+
+    // キー入力処理
+    loop {
+        // キー入力待ち
+        let Ok(key) = terminal.getch() else {
+            continue;
+        };
+        if config.keys.is(Action::Quit, &key) {
+            break;
+        }
+        let mut solving = solving.lock().unwrap();
+        let Solving { pack, index, attempt, .. } = &mut *solving;
+        if let Some(op) = config.keys.operation(&key) {
+            attempt.operate(op);
+            attempt.game.take_events();
+        } else if config.keys.is(Action::Undo, &key) {
+            attempt.undo();
+        } else {
+            match key {
+                Key::Char('r') => {
+                    attempt.retry();
+                }
+                Key::Char('n') if *index + 1 < pack.len() => {
+                    *index += 1;
+                    *attempt = Attempt::new(&pack[*index], config.rules.puzzle);
+                }
+                Key::Char('p') if *index > 0 => {
+                    *index -= 1;
+                    *attempt = Attempt::new(&pack[*index], config.rules.puzzle);
+                }
+                _ => continue,  // 何もしない
+            }
+        }
+        draw_puzzle(&mut solving, config);
+    }
+}
+
+// パズル中の画面を描画する
+fn draw_puzzle(Solving { pack, index, attempt, renderer }: &mut Solving, config: &Config) {
+    let index = *index;
+    draw(&attempt.game, &config.visual, renderer);
+    let limit = attempt.limit.map(|limit| format!("/{limit}")).unwrap_or_default();
+    renderer.put_text(1, 24, &format!(
+        "puzzle {}/{} {}  goal: {}  pieces {}{limit}",
+        index + 1,
+        pack.len(),
+        pack[index].name,
+        attempt.goal,
+        attempt.placed,
+    ));
+    let quit = config.keys.label(Action::Quit);
+    let undo = config.keys.label(Action::Undo);
+    let status = match attempt.state {
+        State::Solved if index + 1 < pack.len() => format!("SOLVED!  [n] next puzzle  [r] retry  [{quit}] quit"),
+        State::Solved => format!("SOLVED! pack complete  [r] retry  [{quit}] quit"),
+        State::Failed => format!("FAILED  [{undo}] undo  [r] retry  [{quit}] quit"),
+        State::Playing => format!("[{undo}] undo  [r] retry  [n/p] next/prev puzzle  [{quit}] quit"),
+    };
+    renderer.put_text(1, 25, &status);
+    renderer.flush();
+}
+
+// キー割り当ての変更画面の状態
+struct Rebind {
+    config: Config,
+    selected: usize,          // 選んでいる操作(`ACTIONS`の添字)
+    capturing: bool,          // 割り当てるキーの入力待ちか
+    message: Option<String>,  // 直前の操作の結果
+    renderer: Renderer,       // 画面の差分描画
+}
+
+// キー割り当ての変更
+// 次のキーは割り当てに関係なく固定
+// - 上下キー: 操作を選ぶ
+// - Enterキー: 選んだ操作にキーを追加する(次に押したキーを割り当てる)
+// - Backspace/Deleteキー: 選んだ操作の割り当てをすべて外す
+// - `d`キー: すべてデフォルトの割り当てに戻す
+// - `s`キー: 設定ファイルに保存して終了(保存した設定を返す)
+// - `q`キー: 保存せずに終了
+pub fn rebind(config: Config, path: &Path, terminal: &Terminal) -> Option<Config> {
+    let rebind = Arc::new(Mutex::new(Rebind { config, selected: 0, capturing: false, message: None, renderer: Renderer::new() }));
+
+    draw_rebind(&mut rebind.lock().unwrap(), path);
+    // 端末の大きさが変わったら描き直す
+    let _resize = {
+        let rebind = Arc::clone(&rebind);
+        let path = path.to_owned();
+        term::on_resize(move || draw_rebind(&mut rebind.lock().unwrap(), &path))
+    };
+
+    // キー入力処理
+    loop {
+        // キー入力待ち
+        let Ok(key) = terminal.getch() else {
+            continue;
+        };
+        let mut rebind = rebind.lock().unwrap();
+        let action = ACTIONS[rebind.selected];
+        if rebind.capturing {
+            // 押したキーを割り当てる
+            rebind.capturing = false;
+            rebind.message = match KeyName::from_key(&key) {
+                Some(config::RESERVED_KEY) => Some(format!("{} is reserved for the GUI", config::RESERVED_KEY)),
+                Some(key) => {
+                    rebind.config.keys.bind(action, key);
+                    None
+                }
+                None => Some(format!("{key:?} cannot be bound")),
+            };
+            draw_rebind(&mut rebind, path);
+            continue;
+        }
+        rebind.message = None;
+        match key {
+            Key::Up => {
+                rebind.selected = rebind.selected.checked_sub(1).unwrap_or(ACTIONS.len() - 1);
+            }
+            Key::Down => {
+                rebind.selected = (rebind.selected + 1) % ACTIONS.len();
+            }
+            Key::Char('\r') => {
+                rebind.capturing = true;
+            }
+            Key::Backspace | Key::Delete => {
+                rebind.config.keys.clear(action);
+            }
+            Key::Char('d') => {
+                rebind.config.keys = Bindings::default();
+            }
+            Key::Char('s') => {
+                let saved = rebind.config.validate().and_then(|()| {
+                    config::save(path, &rebind.config)
+                        .map_err(|e| format!("failed to save {}: {e}", path.display()))
+                });
+                match saved {
+                    Ok(()) => return Some(rebind.config.clone()),
+                    Err(e) => rebind.message = Some(e),
+                }
+            }
+            Key::Char('q') => {
+                return None;
+            }
+            _ => continue,  // 何もしない
+        }
+        draw_rebind(&mut rebind, path);
+    }
+}
+
+// キー割り当ての変更画面を描画する
+fn draw_rebind(rebind: &mut Rebind, path: &Path) {
+    let renderer = &mut rebind.renderer;
+    renderer.put_text(3, 2, "KEY BINDINGS");
+    for (i, action) in ACTIONS.iter().enumerate() {
+        let cursor = if i == rebind.selected { ">" } else { " " };
+        renderer.put_text(3, i+4, &format!("{cursor} {:<12}{}", action.name(), rebind.config.keys.label(*action)));
+    }
+    let status = match &rebind.message {
+        _ if rebind.capturing => format!("press a key for {}", ACTIONS[rebind.selected].name()),
+        Some(message) => message.clone(),
+        None => format!("save to {}", path.display()),
+    };
+    renderer.put_text(1, 24, &status);
+    renderer.put_text(1, 25, "[enter] add key  [bs] clear  [d] defaults  [s] save  [q] cancel");
+    renderer.flush();
 }
 
 // オートプレイ
-pub fn auto(weight: GenoSeq) {
+pub fn auto(weight: GenoSeq, spectator: Option<Spectator>, config: &Config, terminal: &Terminal) {
     // 自動化処理
-    let _ = thread::spawn(move || {
-        let mut game = Game::new();
-        // 画面クリア
-        println!("\x1b[2J\x1b[H\x1b[?25l");
+    let quit = Arc::new(AtomicBool::new(false));
+    let thread_config = config.clone();
+    let thread_quit = Arc::clone(&quit);
+    let handle = thread::spawn(move || {
+        let config = thread_config;
+        let mut game = Game::with_rules(config.rules.auto);
+        let mut renderer = Renderer::new();
         // フィールドを描画
-        draw(&game);
+        draw(&game, &config.visual, &mut renderer);
+        renderer.flush();
 
-        loop {
+        while !thread_quit.load(Ordering::Relaxed) {
             // 指定した遺伝子で評価後のエリート個体を取得
             let elite = eval(&game, &weight);
             game = elite;
-            // エリート個体のブロックを落下
-            if landing(&mut game).is_err() {
+            // エリート個体のブロックを落下(見えなくなるまでの時間は待たない)
+            let landed = landing(&mut game);
+            fade_all(&mut game);
+            if landed.is_err() {
                 // ブロックを生成できないならゲームオーバー
-                gameover(&game);
+                broadcast(&mut game, spectator.as_ref());
+                gameover(&game, &config, &mut renderer);
+                renderer.flush();
                 break;
             }
-            draw(&game);
+            broadcast(&mut game, spectator.as_ref());
+            draw(&game, &config.visual, &mut renderer);
+            renderer.flush();
         }
     });
 
     // キー入力処理
-    let g = Getch::new();
     loop {
-        // `q`キーで終了
-        if let Ok(Key::Char('q')) = g.getch() {
-            break;
+        // 終了キーで終了
+        if let Ok(key) = terminal.getch() {
+            if config.keys.is(Action::Quit, &key) {
+                break;
+            }
+        }
+    }
+
+    // 終了処理(描画が終わるのを待つ)
+    quit.store(true, Ordering::Relaxed);
+    handle.join().ok();
+}
+
+// 対戦の状態
+struct Versus {
+    game: Game,
+    stream: TcpStream,                  // 送信用のストリーム
+    opponent: Field,                    // 相手のフィールド
+    opponent_score: usize,              // 相手のスコア
+    garbage: VecDeque<(usize, usize)>,  // 受け取ったおじゃまライン(段数, 穴の列)
+    result: Option<&'static str>,       // 対戦結果
+    entering: Option<Instant>,          // 出現待ち(ARE)を始めた時刻
+    renderer: Renderer,                 // 画面の差分描画
+    quit: bool,                         // 終了したか(受信や自然落下のスレッドを止める)
+}
+
+impl Versus {
+    fn new(stream: &TcpStream, seed: u64, rules: Ruleset) -> Versus {
+        // おじゃまライン(`add_garbage`と穴の列)と相手の盤面(`draw_opponent`と`net::Message::Board`)は通常の大きさのフィールドを前提にしている
+        // でかブロックの対戦は`Config::validate`で拒否している
+        debug_assert!(!rules.big, "big blocks are not supported in versus");
+        Versus {
+            game: Game::with_seed_and_rules(seed, rules),
+            stream: stream.try_clone().unwrap(),
+            opponent: EMPTY_FIELD,
+            opponent_score: 0,
+            garbage: VecDeque::new(),
+            result: None,
+            entering: None,
+            renderer: Renderer::new(),
+            quit: false,
+        }
+    }
+}
+
+// 対戦プレイ
+// 対戦相手と同じシード値を使い、同じ順番でブロックを出現させる
+pub fn versus(stream: TcpStream, seed: u64, config: &Config, terminal: &Terminal) {
+    let vs = Arc::new(Mutex::new(Versus::new(&stream, seed, config.rules.versus)));
+
+    // フィールドを描画
+    draw_versus(&mut vs.lock().unwrap(), config);
+    // 端末の大きさが変わったら描き直す
+    let _resize = {
+        let vs = Arc::clone(&vs);
+        let config = config.clone();
+        term::on_resize(move || draw_versus(&mut vs.lock().unwrap(), &config))
+    };
+
+    // 受信処理
+    {
+        let vs = Arc::clone(&vs);
+        let config = config.clone();
+        let mut stream = stream;
+        let _ = thread::spawn(move || {
+            loop {
+                let msg = net::recv(&mut stream);
+                let mut vs = vs.lock().unwrap();
+                if vs.quit || vs.result.is_some() {
+                    break;
+                }
+                match msg {
+                    Ok(Message::Board { field, score }) => {
+                        vs.opponent = *field;
+                        vs.opponent_score = score;
+                    }
+                    Ok(Message::Garbage { lines, hole }) => {
+                        vs.garbage.push_back((lines as usize, hole as usize));
+                    }
+                    Ok(Message::GameOver) => {
+                        versus_result(&mut vs, "YOU WIN", &config);
+                        break;
+                    }
+                    Ok(Message::Hello { .. }) => (),  // 何もしない
+                    Err(_) => {
+                        versus_result(&mut vs, "DISCONNECTED", &config);
+                        break;
+                    }
+                }
+                draw_versus(&mut vs, &config);
+            }
+        });
+    }
+
+    // 自然落下処理
+    {
+        let vs = Arc::clone(&vs);
+        let shared = Arc::clone(&vs);
+        let config = config.clone();
+        let _ = thread::spawn(move || {
+            loop {
+                // nミリ秒間スリーブする
+                let sleep_msec = {
+                    let game = &vs.lock().unwrap().game;
+                    game.rules.gravity.msec(game.line)
+                };
+                thread::sleep(time::Duration::from_millis(sleep_msec));
+                // 自然落下
+                let mut vs = vs.lock().unwrap();
+                if vs.quit || vs.result.is_some() {
+                    break;
+                }
+                if operate(&mut vs.game, Operation::Gravity) && versus_landing(&mut vs).is_err() {
+                    // ブロックを生成できないなら負け
+                    net::send(&mut vs.stream, &Message::GameOver).ok();
+                    versus_result(&mut vs, "YOU LOSE", &config);
+                    break;
+                }
+                versus_wait_entry(&shared, &mut vs, &config);
+                // フィールドを描画
+                draw_versus(&mut vs, &config);
+            }
+        });
+    }
+
+    // キー入力処理
+    loop {
+        // キー入力待ち
+        match terminal.getch() {
+            Ok(key) if config.keys.is(Action::Quit, &key) => {
+                break;
+            }
+            Ok(key) => {
+                let Some(op) = config.keys.operation(&key) else {
+                    continue;
+                };
+                let shared = Arc::clone(&vs);
+                let mut vs = vs.lock().unwrap();
+                if vs.result.is_some() {
+                    // 決着後は操作を受け付けない
+                    continue;
+                }
+                if operate(&mut vs.game, op) && versus_landing(&mut vs).is_err() {
+                    // ブロックを生成できないなら負け
+                    net::send(&mut vs.stream, &Message::GameOver).ok();
+                    versus_result(&mut vs, "YOU LOSE", config);
+                    continue;
+                }
+                versus_wait_entry(&shared, &mut vs, config);
+                draw_versus(&mut vs, config);
+            }
+            _ => (),  // 何もしない
         }
     }
 
     // 終了処理
-    quit();
+    let mut vs = vs.lock().unwrap();
+    vs.quit = true;
+    vs.stream.shutdown(std::net::Shutdown::Both).ok();
+}
+
+// AIの対戦相手が1つのブロックを置く間隔(ミリ秒)
+const AI_OPPONENT_MSEC: u64 = 1000;
+
+// AIの対戦相手
+// ホストに接続し、人間の対戦相手と同じメッセージをやり取りしながら指定した遺伝子で評価して置く
+// 接続できなければエラーを返す(接続後に失敗した場合は切断するので、ホストの待ち受けも失敗する)
+pub fn ai_opponent(addr: SocketAddr, weight: GenoSeq, rules: Ruleset) -> io::Result<()> {
+    let stream = TcpStream::connect(addr)?;
+    let _ = thread::spawn(move || {
+        let Ok((stream, seed)) = net::handshake(stream, &rules) else {
+            return;
+        };
+        let vs = Arc::new(Mutex::new(Versus::new(&stream, seed, rules)));
+
+        // 受信処理(おじゃまラインのみ使い、決着か切断で終了する)
+        {
+            let vs = Arc::clone(&vs);
+            let mut stream = stream;
+            let _ = thread::spawn(move || {
+                loop {
+                    let msg = net::recv(&mut stream);
+                    let mut vs = vs.lock().unwrap();
+                    match msg {
+                        Ok(Message::Garbage { lines, hole }) => {
+                            vs.garbage.push_back((lines as usize, hole as usize));
+                        }
+                        Ok(Message::Board { .. } | Message::Hello { .. }) => (),  // 何もしない
+                        Ok(Message::GameOver) | Err(_) => {
+                            vs.quit = true;
+                            break;
+                        }
+                    }
+                }
+            });
+        }
+
+        loop {
+            thread::sleep(time::Duration::from_millis(AI_OPPONENT_MSEC));
+            let mut vs = vs.lock().unwrap();
+            if vs.quit {
+                break;
+            }
+            // 指定した遺伝子で評価後のエリート個体のブロックを落下
+            vs.game = eval(&vs.game, &weight);
+            if versus_landing(&mut vs).is_err() {
+                // ブロックを生成できないなら負け
+                net::send(&mut vs.stream, &Message::GameOver).ok();
+                break;
+            }
+        }
+    });
+    Ok(())
+}
+
+// 対戦で出現待ちが始まったら、待ち時間の経過後に次のブロックを出現させる
+fn versus_wait_entry(shared: &Arc<Mutex<Versus>>, vs: &mut Versus, config: &Config) {
+    let Some(entry) = vs.game.entry.filter(|_| vs.entering.is_none()) else {
+        return;
+    };
+    let started = Instant::now();
+    vs.entering = Some(started);
+    let vs = Arc::clone(shared);
+    let config = config.clone();
+    let _ = thread::spawn(move || {
+        thread::sleep(time::Duration::from_millis(entry.delay));
+        let mut vs = vs.lock().unwrap();
+        if vs.quit || vs.result.is_some() || vs.entering != Some(started) {
+            return;
+        }
+        vs.entering = None;
+        operate(&mut vs.game, Operation::Entry);
+        draw_versus(&mut vs, &config);
+    });
+}
+
+// 対戦時のブロック落下後の処理
+// - 消したライン数に応じて相手におじゃまラインを送る(受け取ったおじゃまラインと相殺する)
+// - ラインを消さなかった場合は受け取ったおじゃまラインをせり上げる
+fn versus_landing(vs: &mut Versus) -> Result<(), ()> {
+    let line = vs.game.line;
+    landing(&mut vs.game)?;
+    // おじゃまラインでマスがずれるため、見えなくなるまでの時間は待たない
+    fade_all(&mut vs.game);
+    let erased = vs.game.line - line;
+    // 受け取ったおじゃまラインと相殺
+    let mut attack = GARBAGE_TABLE[erased];
+    while attack > 0 {
+        let Some((lines, _)) = vs.garbage.front_mut() else {
+            break;
+        };
+        let offset = attack.min(*lines);
+        *lines -= offset;
+        attack -= offset;
+        if *lines == 0 {
+            vs.garbage.pop_front();
+        }
+    }
+    // 残った分を相手に送る
+    if attack > 0 {
+        let hole = rand::random::<u8>() % (FIELD_WIDTH-4) as u8;
+        net::send(&mut vs.stream, &Message::Garbage { lines: attack as u8, hole }).ok();
+    }
+    // ラインを消さなかったらおじゃまラインをせり上げる
+    if erased == 0 {
+        while let Some((lines, hole)) = vs.garbage.pop_front() {
+            add_garbage(&mut vs.game.field, lines, hole)?;
+        }
+        if is_collision(&vs.game.field, &vs.game.pos, &vs.game.block) {
+            return Err(());
+        }
+    }
+    // 固定後のフィールドを相手に送る
+    let msg = Message::Board { field: Box::new(vs.game.field), score: vs.game.score };
+    net::send(&mut vs.stream, &msg).ok();
+    // 対戦中はイベントを使用しないため破棄する
+    vs.game.events.clear();
+    Ok(())
+}
+
+// 自分と相手のフィールドを描画する
+fn draw_versus(vs: &mut Versus, config: &Config) {
+    draw(&vs.game, &config.visual, &mut vs.renderer);
+    draw_opponent(&vs.opponent, vs.opponent_score, &config.visual, &mut vs.renderer);
+    if let Some(result) = vs.result {
+        vs.renderer.put_text(1, 25, result);
+        vs.renderer.put_text(1, 26, &format!("press `{}` key to exit", config.keys.label(Action::Quit)));
+    }
+    vs.renderer.flush();
+}
+
+// 対戦結果を記録して描画する
+fn versus_result(vs: &mut Versus, result: &'static str, config: &Config) {
+    vs.result = Some(result);
+    draw_versus(vs, config);
 }
@@ -0,0 +1,274 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use crate::game::{Game, Setup, Operation, Event, Ruleset, EMPTY_FIELD, operate, landing, enter, fade_all};
+use crate::notation;
+use crate::fumen;
+use crate::history::History;
+
+// パズルファイル
+//
+// ```text
+// goal: tsd
+// hold: T
+// queue: IOS
+// .........XX
+// X...XXXXXXX
+// XX.XXXXXXXX
+// ```
+//
+// - `goal:`は必須で、`lines N`、`pc`、`tsd`、`survive N`のいずれか
+// - 盤面はテキスト表記で書くか、`fumen:`でテト譜(v115)を指定する
+// - `queue:`で指定したブロックを置き切るまでに目標を達成できなければ失敗
+
+// パズルの目標
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Goal {
+    Lines(usize),    // nライン消す
+    PerfectClear,    // パーフェクトクリア
+    TSpinDouble,     // Tスピンダブル
+    Survive(usize),  // nブロック置くまで生き残る
+}
+
+impl FromStr for Goal {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Goal, String> {
+        let s = s.trim().to_ascii_lowercase();
+        let (kind, arg) = s.split_once(' ').unwrap_or((&s, ""));
+        let count = || arg.trim().parse().map_err(|_| format!("invalid goal count: {arg}"));
+        match kind {
+            "lines" => Ok(Goal::Lines(count()?)),
+            "pc" => Ok(Goal::PerfectClear),
+            "tsd" => Ok(Goal::TSpinDouble),
+            "survive" => Ok(Goal::Survive(count()?)),
+            _ => Err(format!("unknown goal: {s}")),
+        }
+    }
+}
+
+impl fmt::Display for Goal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Goal::Lines(n) => write!(f, "clear {n} lines"),
+            Goal::PerfectClear => write!(f, "perfect clear"),
+            Goal::TSpinDouble => write!(f, "T-spin double"),
+            Goal::Survive(n) => write!(f, "survive {n} pieces"),
+        }
+    }
+}
+
+// パズル
+#[derive(Clone)]
+pub struct Puzzle {
+    pub name: String,
+    pub setup: Setup,
+    pub goal: Goal,
+}
+
+// パズルファイルを読み込む
+pub fn parse(name: &str, s: &str) -> Result<Puzzle, String> {
+    let mut goal = None;
+    let mut fumen = None;
+    let mut board = String::new();
+    for line in s.lines() {
+        let trimmed = line.trim();
+        if let Some(value) = trimmed.strip_prefix("goal:") {
+            goal = Some(value.parse()?);
+        } else if let Some(value) = trimmed.strip_prefix("fumen:") {
+            fumen = Some(value.trim());
+        } else {
+            board.push_str(line);
+            board.push('\n');
+        }
+    }
+    let goal = goal.ok_or("missing goal")?;
+    let board = notation::parse(&board)?;
+    let setup = match fumen {
+        Some(fumen) => {
+            if board.field != EMPTY_FIELD {
+                return Err("fumen and board rows cannot be combined".to_owned());
+            }
+            // `hold:`/`queue:`があればテト譜のコメントより優先する
+            let mut setup = fumen::decode(fumen).map_err(|e| format!("invalid fumen: {e}"))?;
+            if board.hold.is_some() {
+                setup.hold = board.hold;
+            }
+            if !board.queue.is_empty() {
+                setup.queue = board.queue;
+            }
+            setup
+        }
+        None => board,
+    };
+    Ok(Puzzle { name: name.to_owned(), setup, goal })
+}
+
+// パズルファイルを読み込む
+pub fn load(path: &Path) -> Result<Puzzle, String> {
+    let name = path.file_stem().unwrap_or_default().to_string_lossy();
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    parse(&name, &text).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+// パズルパックを読み込む
+// ディレクトリを指定した場合は中のファイルをファイル名順に読み込む
+pub fn load_pack(path: &Path) -> Result<Vec<Puzzle>, String> {
+    if !path.is_dir() {
+        return Ok(vec![load(path)?]);
+    }
+    let mut paths = fs::read_dir(path)
+        .map_err(|e| format!("{}: {e}", path.display()))?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && !path.file_name().unwrap_or_default().to_string_lossy().starts_with('.'))
+        .collect::<Vec<_>>();
+    if paths.is_empty() {
+        return Err(format!("{}: no puzzle files", path.display()));
+    }
+    paths.sort();
+    paths.iter().map(|path| load(path)).collect()
+}
+
+// パズルの状態
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum State {
+    Playing,
+    Solved,
+    Failed,
+}
+
+// パズルの挑戦
+pub struct Attempt {
+    pub goal: Goal,
+    pub game: Game,
+    pub placed: usize,         // 置いたブロックの数
+    pub limit: Option<usize>,  // 置けるブロックの数
+    pub state: State,
+    start: Game,               // やり直し用の初期状態
+    history: History,          // 1手戻す用の履歴
+}
+
+impl Attempt {
+    pub fn new(puzzle: &Puzzle, rules: Ruleset) -> Attempt {
+        let game = Game::with_rules(rules).with_setup(puzzle.setup.clone());
+        let limit = match puzzle.goal {
+            Goal::Survive(n) => Some(n),
+            _ if puzzle.setup.queue.is_empty() => None,
+            _ => Some(puzzle.setup.queue.len()),
+        };
+        Attempt {
+            goal: puzzle.goal,
+            start: game.clone(),
+            history: History::new(&game, usize::MAX),
+            game,
+            placed: 0,
+            limit,
+            state: State::Playing,
+        }
+    }
+
+    // 操作を適用する
+    // ブロックを固定したら目標を達成したか判定する
+    pub fn operate(&mut self, op: Operation) {
+        if self.state != State::Playing {
+            return;
+        }
+        if !operate(&mut self.game, op) {
+            return;
+        }
+        let over = landing(&mut self.game).is_err();
+        // 自然落下がないので出現待ちも見えなくなるまでの時間も待たない
+        enter(&mut self.game);
+        fade_all(&mut self.game);
+        self.history.push(&self.game);
+        self.placed += 1;
+        let (lines, tspin, pc) = self.game
+            .take_events()
+            .into_iter()
+            .find_map(|event| match event {
+                Event::Lock { lines, tspin, pc } => Some((lines, tspin, pc)),
+                _ => None,
+            })
+            .unwrap_or_default();
+        let solved = match self.goal {
+            Goal::Lines(n) => self.game.line >= n,
+            Goal::PerfectClear => pc,
+            Goal::TSpinDouble => tspin && lines == 2,
+            Goal::Survive(n) => !over && self.placed >= n,
+        };
+        self.state = if solved {
+            State::Solved
+        } else if over || self.limit.is_some_and(|limit| self.placed >= limit) {
+            State::Failed
+        } else {
+            State::Playing
+        };
+    }
+
+    // 1つ前のブロックが出現した時点に戻す
+    pub fn undo(&mut self) {
+        if self.history.undo(&mut self.game) {
+            self.placed -= 1;
+            self.state = State::Playing;
+        }
+    }
+
+    // 最初からやり直す
+    pub fn retry(&mut self) {
+        self.game = self.start.clone();
+        self.history = History::new(&self.game, usize::MAX);
+        self.placed = 0;
+        self.state = State::Playing;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let puzzle = parse("tsd", "goal: tsd\nqueue: T\n..X........\nXX...XXXXXX\n").unwrap();
+        assert_eq!(puzzle.goal, Goal::TSpinDouble);
+        assert_eq!(puzzle.setup.queue.len(), 1);
+        let puzzle = parse("fumen", "goal: lines 4\nfumen: v115@9gF8DeF8DeF8DeF8NeAgH\nqueue: I").unwrap();
+        assert_eq!(puzzle.goal, Goal::Lines(4));
+        assert_ne!(puzzle.setup.field, EMPTY_FIELD);
+        assert!(parse("none", "...........").is_err());
+        assert!(parse("bad", "goal: lines").is_err());
+    }
+
+    #[test]
+    fn test_attempt() {
+        // Iブロックを右端に落とすと4ライン消し
+        let puzzle = parse("tetris", "
+            goal: lines 4
+            queue: IO
+            XXXXXXXXXX.
+            XXXXXXXXXX.
+            XXXXXXXXXX.
+            XXXXXXXXXX.
+        ").unwrap();
+        let mut attempt = Attempt::new(&puzzle, Ruleset::default());
+        assert_eq!(attempt.limit, Some(2));
+        // 回転せずに落とすと失敗
+        attempt.operate(Operation::HardDrop);
+        attempt.operate(Operation::HardDrop);
+        assert_eq!(attempt.state, State::Failed);
+        attempt.undo();
+        attempt.undo();
+        assert_eq!((attempt.state, attempt.placed), (State::Playing, 0));
+        // 縦にして右端に落とすと成功
+        attempt.operate(Operation::RotateRight);
+        for _ in 0..6 {
+            attempt.operate(Operation::MoveRight);
+        }
+        attempt.operate(Operation::HardDrop);
+        assert_eq!(attempt.state, State::Solved);
+        attempt.retry();
+        assert_eq!(attempt.game.field, attempt.start.field);
+        assert_eq!(attempt.placed, 0);
+    }
+}
@@ -0,0 +1,214 @@
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use crate::term;
+
+// 描画内容の座標系(キャンバス)の大きさ
+// 1～35桁が盤面(フィールド、ホールド、ネクスト)、36桁以降がサイドパネル(フィネス、統計、対戦相手)、
+// 24行目以降が状態表示やハイスコア表
+const CANVAS_WIDTH: usize = 80;
+const CANVAS_HEIGHT: usize = 40;
+// 盤面の幅
+const BOARD_WIDTH: usize = 35;
+// サイドパネル(対戦相手のフィールドの右端)まで含めた幅
+const FULL_WIDTH: usize = 65;
+// 状態表示の先頭行
+const STATUS_TOP: usize = 24;
+// 表示に最低限必要な行数(状態表示の2行まで)
+const MIN_HEIGHT: usize = 25;
+// ハイスコア表まで含めた行数
+const FULL_HEIGHT: usize = 37;
+
+// 文字の色情報をリセットするエスケープシーケンス
+const RESET: &str = "\x1b[0m";
+
+// 画面の1文字
+#[derive(Clone, Copy, PartialEq)]
+struct Cell {
+    style: &'static str,  // 色情報のエスケープシーケンス
+    ch: char,
+}
+
+// 端末の大きさに合わせたキャンバスの配置
+struct Layout {
+    left: usize,  // キャンバスの1桁目を置く位置(0始まり)
+    top: usize,   // キャンバスの1行目を置く位置(0始まり)
+    side: bool,   // サイドパネルを表示するか
+}
+
+impl Layout {
+    // 盤面が収まらない場合は`None`を返す
+    // 幅が足りなければサイドパネルを隠して盤面だけを中央に置く
+    fn new(cols: usize, rows: usize) -> Option<Layout> {
+        if cols < BOARD_WIDTH || rows < MIN_HEIGHT {
+            return None;
+        }
+        let side = cols >= FULL_WIDTH;
+        let width = if side { FULL_WIDTH } else { BOARD_WIDTH };
+        Some(Layout {
+            left: (cols - width) / 2,
+            // 縦方向も中央に置くが、ハイスコア表が収まるように上に寄せる
+            top: ((rows - MIN_HEIGHT) / 2).min(rows.saturating_sub(FULL_HEIGHT)),
+            side,
+        })
+    }
+}
+
+// 差分描画を行うダブルバッファ
+// キャンバスに書き込んだ内容を端末の中央に配置し、前回描画した画面から変化した文字だけを1回の書き込みで出力する
+pub struct Renderer {
+    canvas: Vec<Option<Cell>>,  // 次に描画する内容(キャンバスの座標)
+    screen: Vec<Option<Cell>>,  // 前回描画した画面(端末の座標)
+    size: (usize, usize),       // 前回描画したときの端末の大きさ(桁数, 行数)
+}
+
+impl Renderer {
+    pub fn new() -> Renderer {
+        Renderer {
+            canvas: vec![None; CANVAS_WIDTH * CANVAS_HEIGHT],
+            screen: vec![],
+            size: (0, 0),
+        }
+    }
+
+    // 1文字書き込む(座標は1始まり、キャンバスの外は無視する)
+    fn put(&mut self, x: usize, y: usize, cell: Cell) {
+        if (1..=CANVAS_WIDTH).contains(&x) && (1..=CANVAS_HEIGHT).contains(&y) {
+            self.canvas[(y - 1) * CANVAS_WIDTH + x - 1] = Some(cell);
+        }
+    }
+
+    // 色/文字定義(`COLOR_TABLE`の要素)のブロックを1マス(2文字)書き込む
+    pub fn put_block(&mut self, x: usize, y: usize, block: &'static str) {
+        let (style, chars) = block.split_at(block.len() - 2);
+        for (i, ch) in chars.chars().enumerate() {
+            self.put(x + i, y, Cell { style, ch });
+        }
+    }
+
+    // 文字列を書き込む
+    pub fn put_text(&mut self, x: usize, y: usize, text: &str) {
+        for (i, ch) in text.chars().enumerate() {
+            self.put(x + i, y, Cell { style: RESET, ch });
+        }
+    }
+
+    // 端末の大きさに合わせて配置した画面と、前回の描画からの差分を出力するエスケープシーケンスを生成する
+    // 端末の大きさが変わった場合は画面を消去してすべて描き直す
+    fn render(&mut self, cols: usize, rows: usize) -> String {
+        let mut out = String::new();
+        if self.size != (cols, rows) {
+            out.push_str("\x1b[2J");
+            self.screen = vec![None; cols * rows];
+            self.size = (cols, rows);
+        }
+        let mut next = vec![None; cols * rows];
+        if let Some(layout) = Layout::new(cols, rows) {
+            for (i, cell) in self.canvas.iter().enumerate() {
+                let (x, y) = (i % CANVAS_WIDTH, i / CANVAS_WIDTH);
+                if !layout.side && x >= BOARD_WIDTH && y + 1 < STATUS_TOP {
+                    continue;
+                }
+                let (x, y) = (x + layout.left, y + layout.top);
+                if x < cols && y < rows {
+                    next[y * cols + x] = *cell;
+                }
+            }
+        } else {
+            // 盤面が収まらない場合は画面を崩さずにメッセージだけを表示する
+            let message = [
+                "terminal too small".to_owned(),
+                format!("{cols}x{rows}, need {BOARD_WIDTH}x{MIN_HEIGHT}"),
+            ];
+            for (y, line) in message.iter().enumerate().take(rows) {
+                for (x, ch) in line.chars().enumerate().take(cols) {
+                    next[y * cols + x] = Some(Cell { style: RESET, ch });
+                }
+            }
+        }
+        diff(&self.screen, &next, cols, &mut out);
+        self.screen = next;
+        self.canvas.fill(None);
+        out
+    }
+
+    // 差分を1回の書き込みで出力する
+    pub fn flush(&mut self) {
+        let (cols, rows) = term::size().unwrap_or((CANVAS_WIDTH, CANVAS_HEIGHT));
+        let out = self.render(cols, rows);
+        if out.is_empty() {
+            return;
+        }
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(out.as_bytes()).ok();
+        stdout.flush().ok();
+    }
+}
+
+// 前回描画した画面から変化した文字だけを出力する
+// 前回描画して今回書き込まなかった文字は空白で消す
+fn diff(prev: &[Option<Cell>], next: &[Option<Cell>], cols: usize, out: &mut String) {
+    let mut cursor = None;  // 出力後のカーソル位置(0始まり)
+    let mut style = None;   // 出力後の色情報
+    for (i, (prev, next)) in prev.iter().zip(next).enumerate() {
+        if prev == next {
+            continue;
+        }
+        let Some(cell) = next.or(prev.map(|_| Cell { style: RESET, ch: ' ' })) else {
+            continue;
+        };
+        let (x, y) = (i % cols, i / cols);
+        // カーソルの移動(同じ行の右側なら相対移動)
+        match cursor {
+            Some((cx, cy)) if cy == y && cx == x => (),
+            Some((cx, cy)) if cy == y && cx < x => {
+                let _ = write!(out, "\x1b[{}C", x - cx);
+            }
+            _ => {
+                let _ = write!(out, "\x1b[{};{}H", y + 1, x + 1);
+            }
+        }
+        if style != Some(cell.style) {
+            out.push_str(cell.style);
+            style = Some(cell.style);
+        }
+        out.push(cell.ch);
+        cursor = Some((x + 1, y));
+    }
+    if style.is_some_and(|style| style != RESET) {
+        out.push_str(RESET);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let mut renderer = Renderer::new();
+        renderer.put_text(3, 2, "abcd");
+        renderer.put_block(1, 1, "\x1b[41m[]");
+        assert_eq!(renderer.render(35, 25), "\x1b[2J\x1b[1;1H\x1b[41m[]\x1b[2;3H\x1b[0mabcd");
+        // 変化がなければ何も出力しない
+        renderer.put_text(3, 2, "abcd");
+        renderer.put_block(1, 1, "\x1b[41m[]");
+        assert_eq!(renderer.render(35, 25), "");
+        // 変化した文字だけを出力する
+        renderer.put_text(3, 2, "xbcy");
+        renderer.put_block(1, 1, "\x1b[41m[]");
+        assert_eq!(renderer.render(35, 25), "\x1b[2;3H\x1b[0mx\x1b[2Cy");
+        // 書き込まなかった文字は消す
+        renderer.put_block(1, 1, "\x1b[41m[]");
+        assert_eq!(renderer.render(35, 25), "\x1b[2;3H\x1b[0m    ");
+        // 端末の大きさが変わったら中央に配置してすべて描き直す(幅が足りなければサイドパネルは隠す)
+        renderer.put_block(1, 1, "\x1b[41m[]");
+        renderer.put_text(40, 2, "side");
+        assert_eq!(renderer.render(45, 27), "\x1b[2J\x1b[1;6H\x1b[41m[]\x1b[0m");
+        renderer.put_block(1, 1, "\x1b[41m[]");
+        renderer.put_text(40, 2, "side");
+        assert_eq!(renderer.render(67, 40), "\x1b[2J\x1b[4;2H\x1b[41m[]\x1b[5;41H\x1b[0mside");
+        // 盤面が収まらなければメッセージを表示する
+        renderer.put_block(1, 1, "\x1b[41m[]");
+        assert_eq!(renderer.render(30, 25), "\x1b[2J\x1b[1;1H\x1b[0mterminal too small\x1b[2;1H30x25, need 35x25");
+    }
+}
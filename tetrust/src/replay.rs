@@ -0,0 +1,267 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use serde::{Serialize, Deserialize};
+use crate::game::{Game, Operation, Ruleset, operate, landing};
+
+// リプレイファイルのバージョン
+// 形式を変更した場合は数値を上げる
+pub const REPLAY_VERSION: u32 = 2;
+
+// リプレイ
+// 同じシード値から記録した操作を順に適用するとゲームを再現できる
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    pub seed: u64,
+    pub settings: Settings,
+    pub rules: Ruleset,  // 記録時のルール(ブロックの生成方式を含む)
+    pub inputs: Vec<Input>,
+    pub score: usize,  // 最終スコア
+    pub line: usize,   // 最終ライン数
+}
+
+// 記録時の操作設定
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Settings {
+    pub das: Option<u64>,  // 横移動のリピート開始までの時間(ミリ秒) 端末のキーリピートに任せる場合は`None`
+    pub arr: Option<u64>,  // 横移動のリピート間隔(ミリ秒) 端末のキーリピートに任せる場合は`None`
+    pub gravity: u64,      // 開始時の自然落下の間隔(ミリ秒)
+}
+
+// 記録した操作
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Input {
+    pub time: u64,  // ゲーム開始からの経過時間(ミリ秒)
+    pub op: Operation,
+}
+
+// バージョンのみを読み込むためのリプレイ
+#[derive(Deserialize)]
+struct ReplayVersion {
+    version: u32,
+}
+
+// リプレイの記録
+pub struct Recorder {
+    path: PathBuf,
+    start: Instant,
+    replay: Replay,
+}
+
+impl Recorder {
+    // ゲーム開始時の状態から記録を始める
+    pub fn new(game: &Game, settings: Settings, path: PathBuf) -> Recorder {
+        Recorder {
+            path,
+            start: Instant::now(),
+            replay: Replay {
+                version: REPLAY_VERSION,
+                seed: game.seed,
+                settings,
+                rules: game.rules,
+                inputs: vec![],
+                score: 0,
+                line: 0,
+            },
+        }
+    }
+
+    // 新しいゲームの記録をやり直す(同じファイルに書き出す)
+    pub fn restart(&mut self, game: &Game) {
+        *self = Recorder::new(game, self.replay.settings, self.path.clone());
+    }
+
+    // 操作を記録する
+    pub fn record(&mut self, op: Operation) {
+        let time = self.start.elapsed().as_millis() as u64;
+        self.replay.inputs.push(Input { time, op });
+    }
+
+    // 最終結果を記録してファイルに書き出す
+    pub fn save(mut self, game: &Game) -> io::Result<()> {
+        self.replay.score = game.score;
+        self.replay.line = game.line;
+        let writer = BufWriter::new(File::create(&self.path)?);
+        serde_json::to_writer(writer, &self.replay)?;
+        Ok(())
+    }
+}
+
+// リプレイファイルを読み込む
+// バージョンが異なる場合はエラーを返す
+pub fn load(path: &Path) -> io::Result<Replay> {
+    let mut json = String::new();
+    BufReader::new(File::open(path)?).read_to_string(&mut json)?;
+    // 形式の違う古いリプレイもバージョンの違いとして報告できるように、先にバージョンだけを確認する
+    let ReplayVersion { version } = serde_json::from_str(&json)?;
+    if version != REPLAY_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unsupported replay version {version} (expected {REPLAY_VERSION})"),
+        ));
+    }
+    Ok(serde_json::from_str(&json)?)
+}
+
+// リプレイの再生
+// 記録した操作を順に適用してゲームを再現する
+pub struct Player {
+    pub replay: Replay,
+    pub game: Game,
+    pub piece: usize,   // 固定したブロックの数
+    pub pieces: usize,  // リプレイ全体で固定したブロックの数
+    pub time: u64,      // 最後に適用した操作の時刻(ミリ秒)
+    cursor: usize,      // 次に適用する操作の位置
+    over: bool,         // ゲームオーバーまで再生したか
+}
+
+impl Player {
+    pub fn new(replay: Replay) -> Player {
+        let mut player = Player {
+            game: Game::with_seed_and_rules(replay.seed, replay.rules),
+            replay,
+            piece: 0,
+            pieces: 0,
+            time: 0,
+            cursor: 0,
+            over: false,
+        };
+        // 全体のブロック数を数えておく
+        while player.step() {}
+        player.pieces = player.piece;
+        player.rewind();
+        player
+    }
+
+    // 最初の状態に戻す
+    fn rewind(&mut self) {
+        self.game = Game::with_seed_and_rules(self.replay.seed, self.replay.rules);
+        self.piece = 0;
+        self.time = 0;
+        self.cursor = 0;
+        self.over = false;
+    }
+
+    // 操作を1つ適用する
+    // 最後まで再生済みの場合は`false`を返す
+    fn step(&mut self) -> bool {
+        if self.is_finished() {
+            return false;
+        }
+        let Input { time, op } = self.replay.inputs[self.cursor];
+        self.cursor += 1;
+        self.time = time;
+        if operate(&mut self.game, op) {
+            self.piece += 1;
+            if landing(&mut self.game).is_err() {
+                self.over = true;
+            }
+        }
+        true
+    }
+
+    // 指定した時刻までの操作を適用する
+    // 何か操作を適用した場合は`true`を返す
+    pub fn advance(&mut self, time: u64) -> bool {
+        let mut changed = false;
+        while !self.is_finished() && self.replay.inputs[self.cursor].time <= time {
+            changed |= self.step();
+        }
+        changed
+    }
+
+    // 次のブロックが固定されるまで進める
+    pub fn step_piece(&mut self) {
+        let piece = self.piece;
+        while self.piece == piece && self.step() {}
+    }
+
+    // n個目のブロックが固定された直後まで移動する
+    // 巻き戻す場合は最初から再計算する
+    pub fn seek_piece(&mut self, n: usize) {
+        if n < self.piece {
+            self.rewind();
+        }
+        while self.piece < n && self.step() {}
+    }
+
+    // 最後まで再生したか
+    pub fn is_finished(&self) -> bool {
+        self.over || self.cursor >= self.replay.inputs.len()
+    }
+
+    // 再生結果が記録した最終結果と一致するか
+    pub fn verify(&self) -> bool {
+        self.game.score == self.replay.score && self.game.line == self.replay.line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replay_reproduces_game() {
+        let ops = [
+            Operation::HardDrop,
+            Operation::RotateRight,
+            Operation::MoveLeft,
+            Operation::MoveLeft,
+            Operation::HardDrop,
+            Operation::Hold,
+            Operation::MoveRight,
+            Operation::Gravity,
+            Operation::SoftDrop,
+            Operation::HardDrop,
+            Operation::RotateLeft,
+            Operation::HardDrop,
+        ];
+        // 操作を記録しながらプレイ
+        let mut game = Game::with_seed_and_rules(42, Ruleset { hold_reset: true, ..Ruleset::default() });
+        let settings = Settings { das: None, arr: None, gravity: 1000 };
+        let mut recorder = Recorder::new(&game, settings, PathBuf::new());
+        for op in ops {
+            recorder.record(op);
+            if operate(&mut game, op) {
+                landing(&mut game).unwrap();
+            }
+        }
+        recorder.replay.score = game.score;
+
+        // 保存/読み込みを経ても同じ盤面を再現できる
+        let json = serde_json::to_string(&recorder.replay).unwrap();
+        let replay: Replay = serde_json::from_str(&json).unwrap();
+        assert_eq!(replay, recorder.replay);
+        let mut replayed = Game::with_seed_and_rules(replay.seed, replay.rules);
+        for input in &replay.inputs {
+            if operate(&mut replayed, input.op) {
+                landing(&mut replayed).unwrap();
+            }
+        }
+        assert_eq!(replayed.field, game.field);
+        assert_eq!(replayed.score, replay.score);
+
+        // ブロック単位で進めても戻しても同じ盤面になる
+        let mut player = Player::new(replay);
+        assert_eq!(player.pieces, 4);
+        player.step_piece();
+        player.step_piece();
+        let field = player.game.field;
+        player.seek_piece(3);
+        player.seek_piece(2);
+        assert_eq!(player.piece, 2);
+        assert_eq!(player.game.field, field);
+        player.advance(u64::MAX);
+        assert!(player.is_finished());
+        assert_eq!(player.game.field, game.field);
+
+        // 古いバージョンのリプレイは読み込まない
+        let path = std::env::temp_dir().join(format!("tetrust-replay-test-{}.json", std::process::id()));
+        std::fs::write(&path, r#"{"version":1,"randomizer":"bag7","seed":0}"#).unwrap();
+        let err = load(&path).err().unwrap();
+        assert!(err.to_string().contains("unsupported replay version 1"));
+        std::fs::remove_file(&path).ok();
+    }
+}
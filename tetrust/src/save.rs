@@ -0,0 +1,113 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use serde::{Serialize, Deserialize};
+use crate::game::Game;
+
+// セーブデータのバージョン
+// `Game`の構造を変更した場合は数値を上げる
+pub const SAVE_VERSION: u32 = 2;
+
+// セーブデータ
+#[derive(Serialize, Deserialize)]
+struct SaveData<T> {
+    version: u32,
+    game: T,
+}
+
+// バージョンのみを読み込むためのセーブデータ
+#[derive(Deserialize)]
+struct SaveVersion {
+    version: u32,
+}
+
+// データの保存先ディレクトリ
+// `$XDG_DATA_HOME/tetrust`(未設定の場合は`~/.local/share/tetrust`)
+pub fn data_dir() -> PathBuf {
+    env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))
+        .unwrap_or_default()
+        .join("tetrust")
+}
+
+// デフォルトのセーブデータの保存先
+pub fn default_path() -> PathBuf {
+    data_dir().join("save.json")
+}
+
+// ゲームを保存する
+pub fn save(path: &Path, game: &Game) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer(writer, &SaveData { version: SAVE_VERSION, game })?;
+    Ok(())
+}
+
+// 保存したゲームを読み込む
+// バージョンが異なるセーブデータは読み込まずにエラーを返す
+pub fn load(path: &Path) -> io::Result<Game> {
+    let json = fs::read_to_string(path)?;
+    let SaveVersion { version } = serde_json::from_str(&json)?;
+    if version != SAVE_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unsupported save data version {version} (expected {SAVE_VERSION})"),
+        ));
+    }
+    let data: SaveData<Game> = serde_json::from_str(&json)?;
+    Ok(data.game)
+}
+
+// 保存したゲームを再開する
+// 同じセーブデータから何度も再開できないように、読み込めたら削除する
+pub fn resume(path: &Path) -> io::Result<Game> {
+    let game = load(path)?;
+    fs::remove_file(path)?;
+    Ok(game)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{hold, hard_drop, landing};
+
+    #[test]
+    fn test_save_and_load() {
+        let path = env::temp_dir().join(format!("tetrust-save-test-{}.json", std::process::id()));
+        let mut game = Game::with_seed(1);
+        hard_drop(&mut game);
+        landing(&mut game).unwrap();
+        hold(&mut game);
+        save(&path, &game).unwrap();
+
+        // 保存時の状態から同じ順番でブロックが出現する
+        let mut loaded = load(&path).unwrap();
+        assert_eq!(loaded.field, game.field);
+        assert_eq!(loaded.hold, game.hold);
+        assert!(loaded.holded);
+        for _ in 0..20 {
+            hard_drop(&mut game);
+            hard_drop(&mut loaded);
+            landing(&mut game).ok();
+            landing(&mut loaded).ok();
+            assert_eq!(loaded.block, game.block);
+            assert_eq!(loaded.next, game.next);
+        }
+
+        // 再開するとセーブデータは削除される
+        assert!(resume(&path).unwrap().holded);
+        assert!(!path.exists());
+        assert!(resume(&path).is_err());
+
+        // バージョンが異なるセーブデータは読み込まない
+        fs::write(&path, r#"{"version":1,"game":{}}"#).unwrap();
+        let err = load(&path).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        fs::remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,146 @@
+use std::io::{self, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use serde::Serialize;
+use crate::block::{block_char, shape_color};
+use crate::game::{Game, Event, FIELD_WIDTH, FIELD_HEIGHT};
+
+// 観戦用に配信するゲームの状態
+#[derive(Serialize)]
+struct Snapshot<'a> {
+    events: &'a [Event],   // 前回の配信以降に発生したイベント
+    field: Vec<String>,    // 固定済みのブロック(上の段から順に、1文字1マス)
+    piece: Piece,          // 操作中のブロック
+    hold: Option<char>,    // ホールド中のブロック
+    next: Vec<char>,       // ネクスト
+    score: usize,
+    line: usize,
+}
+
+// 操作中のブロック
+// 座標は壁を除いたフィールドの左上を原点とする
+#[derive(Serialize)]
+struct Piece {
+    kind: char,
+    x: isize,
+    y: usize,
+    shape: Vec<String>,
+}
+
+impl<'a> Snapshot<'a> {
+    fn new(game: &Game, events: &'a [Event]) -> Snapshot<'a> {
+        Snapshot {
+            events,
+            field: game.field[..FIELD_HEIGHT-2]
+                .iter()
+                .map(|row| row[2..FIELD_WIDTH-2].iter().map(|&b| block_char(b)).collect())
+                .collect(),
+            piece: Piece {
+                kind: block_char(shape_color(&game.block)),
+                x: game.pos.x as isize - 2,
+                y: game.pos.y,
+                shape: game.block
+                    .iter()
+                    .map(|row| row.iter().map(|&b| block_char(b)).collect())
+                    .collect(),
+            },
+            hold: game.hold.map(|hold| block_char(shape_color(&hold))),
+            next: game.next
+                .iter()
+                .take(game.rules.next)
+                .map(|next| block_char(shape_color(next)))
+                .collect(),
+            score: game.score,
+            line: game.line,
+        }
+    }
+}
+
+// 接続中の観戦者
+#[derive(Default)]
+struct Audience {
+    clients: Vec<TcpStream>,
+    last: Option<String>,  // 途中から接続した観戦者に送る最新の状態
+}
+
+// 観戦配信サーバ
+// ゲームの状態を1行1JSONで接続中の全ての観戦者に送る
+#[derive(Clone)]
+pub struct Spectator {
+    tx: Sender<String>,
+}
+
+impl Spectator {
+    // 指定したポートで観戦者の接続を待ち受ける
+    pub fn listen(port: u16) -> io::Result<Spectator> {
+        Spectator::with_listener(TcpListener::bind(("127.0.0.1", port))?)
+    }
+
+    fn with_listener(listener: TcpListener) -> io::Result<Spectator> {
+        let audience = Arc::new(Mutex::new(Audience::default()));
+        // 接続受付処理
+        {
+            let audience = Arc::clone(&audience);
+            thread::spawn(move || {
+                for mut stream in listener.incoming().flatten() {
+                    let mut audience = audience.lock().unwrap();
+                    if let Some(last) = &audience.last {
+                        if writeln!(stream, "{last}").is_err() {
+                            continue;
+                        }
+                    }
+                    audience.clients.push(stream);
+                }
+            });
+        }
+        // 配信処理
+        // ゲーム側が観戦者への書き込みで待たされないよう別スレッドで送る
+        let (tx, rx) = mpsc::channel::<String>();
+        thread::spawn(move || {
+            for line in rx {
+                let mut audience = audience.lock().unwrap();
+                // 送信に失敗した観戦者は切断する
+                audience.clients.retain_mut(|client| writeln!(client, "{line}").is_ok());
+                audience.last = Some(line);
+            }
+        });
+        Ok(Spectator { tx })
+    }
+
+    // ゲームの状態を配信する
+    pub fn publish(&self, game: &Game, events: &[Event]) {
+        let snapshot = Snapshot::new(game, events);
+        if let Ok(line) = serde_json::to_string(&snapshot) {
+            self.tx.send(line).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+
+    #[test]
+    fn test_publish() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let spectator = Spectator::with_listener(listener).unwrap();
+        let mut game = Game::with_seed(0);
+        let events = game.take_events();
+        spectator.publish(&game, &events);
+
+        // 接続前に配信された最新の状態を受け取れる
+        let mut reader = BufReader::new(TcpStream::connect(addr).unwrap());
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(json["events"][0]["type"], "spawn");
+        assert_eq!(json["field"].as_array().unwrap().len(), FIELD_HEIGHT-2);
+        assert_eq!(json["field"][0], "...........");
+        assert_eq!(json["next"].as_array().unwrap().len(), game.rules.next);
+        assert_eq!(json["score"], 0);
+    }
+}
@@ -0,0 +1,207 @@
+use std::{env, mem, panic, process, thread};
+use std::io::{self, Write};
+use std::sync::Mutex;
+use getch_rs::{Getch, Key};
+use serde::{Serialize, Deserialize};
+use signal_hook::{consts::{SIGHUP, SIGINT, SIGTERM, SIGWINCH}, iterator::{Handle, Signals}};
+
+// 端末で使う色数
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMode {
+    #[default]
+    Auto,       // 環境変数から判定する
+    Truecolor,  // 24bit色
+    #[serde(rename = "256")]
+    #[value(name = "256")]
+    Ansi256,    // 256色
+    #[serde(rename = "16")]
+    #[value(name = "16")]
+    Ansi16,     // 16色
+    None,       // 色を使わずブロックの種類ごとの文字で描画する
+}
+
+impl ColorMode {
+    // `Auto`なら端末の色数を判定する
+    pub fn resolve(self) -> ColorMode {
+        match self {
+            ColorMode::Auto => detect(
+                env::var("NO_COLOR").ok().as_deref(),
+                env::var("COLORTERM").ok().as_deref(),
+                env::var("TERM").ok().as_deref(),
+            ),
+            mode => mode,
+        }
+    }
+}
+
+// 環境変数`NO_COLOR`、`COLORTERM`、`TERM`から端末の色数を判定する
+// - `NO_COLOR`が空でなければ色を使わない
+// - `COLORTERM`が`truecolor`か`24bit`なら24bit色
+// - `TERM`が未設定か`dumb`なら色を使わない、`256color`を含めば256色、それ以外は16色
+fn detect(no_color: Option<&str>, colorterm: Option<&str>, term: Option<&str>) -> ColorMode {
+    if no_color.is_some_and(|v| !v.is_empty()) {
+        return ColorMode::None;
+    }
+    if matches!(colorterm, Some("truecolor" | "24bit")) {
+        return ColorMode::Truecolor;
+    }
+    match term {
+        None | Some("" | "dumb") => ColorMode::None,
+        Some(term) if term.contains("256color") => ColorMode::Ansi256,
+        Some(_) => ColorMode::Ansi16,
+    }
+}
+
+// 端末の大きさ(桁数, 行数)
+// 端末でない場合などで取得できなければ`None`を返す
+pub fn size() -> Option<(usize, usize)> {
+    // SAFETY: `winsize`は整数のみの構造体で、`TIOCGWINSZ`は渡した構造体に書き込むだけ
+    let mut ws: libc::winsize = unsafe { mem::zeroed() };
+    let ret = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut ws) };
+    (ret == 0 && ws.ws_col > 0 && ws.ws_row > 0).then_some((ws.ws_col as usize, ws.ws_row as usize))
+}
+
+// 端末の大きさの変更の監視(破棄すると`redraw`を呼び出さなくなる)
+pub struct Resize(Option<Handle>);
+
+impl Drop for Resize {
+    fn drop(&mut self) {
+        if let Some(handle) = &self.0 {
+            handle.close();
+        }
+    }
+}
+
+// 端末の大きさが変わる(SIGWINCHを受け取る)たびに`redraw`を呼び出す
+// 返した`Resize`を破棄するまで呼び出し続ける
+#[must_use]
+pub fn on_resize(redraw: impl Fn() + Send + 'static) -> Resize {
+    let Ok(mut signals) = Signals::new([SIGWINCH]) else {
+        return Resize(None);
+    };
+    let handle = signals.handle();
+    let _ = thread::spawn(move || {
+        for _ in signals.forever() {
+            redraw();
+        }
+    });
+    Resize(Some(handle))
+}
+
+// 端末を元に戻すための状態(`Terminal`を生成している間だけ`Some`)
+// パニック時やシグナル受信時にも戻せるようにグローバルに持つ
+static SAVED: Mutex<Option<Saved>> = Mutex::new(None);
+
+// 生成前の端末の状態
+struct Saved {
+    termios: Option<libc::termios>,  // 端末の設定(標準入力が端末でなければ`None`)
+    stdout: String,                  // 元の画面に戻した後に標準出力に表示する内容
+    stderr: String,                  // 元の画面に戻した後に標準エラー出力に表示する内容
+}
+
+// 端末の状態を戻すガード
+// 生成すると代替画面に切り替えてカーソルを隠し、キー入力をrawモード(行バッファ、エコー、シグナルなし)にする
+// 次のいずれの場合も元の画面と端末の設定に戻す
+// - ガードを破棄したとき
+// - パニックしたとき(フックでメッセージを表示する前に戻す)
+// - SIGINT/SIGTERM/SIGHUPを受け取ったとき(戻してから終了する)
+pub struct Terminal {
+    input: Getch,  // キー入力(生成時にrawモードにする)
+}
+
+impl Terminal {
+    pub fn enter() -> Terminal {
+        // rawモードにする前の設定を保存する
+        // SAFETY: `termios`は整数のみの構造体で、`tcgetattr`は渡した構造体に書き込むだけ
+        let mut termios: libc::termios = unsafe { mem::zeroed() };
+        let termios = (unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut termios) } == 0).then_some(termios);
+        *SAVED.lock().unwrap_or_else(|e| e.into_inner()) = Some(Saved { termios, stdout: String::new(), stderr: String::new() });
+        write_out("\x1b[?1049h\x1b[?25l\x1b[2J\x1b[H");
+        // パニック時はメッセージが元の画面に残るように先に戻し、他のスレッドのパニックでも終了する
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            restore();
+            hook(info);
+            process::exit(101);
+        }));
+        // シグナルを受け取ったら戻してから終了する
+        if let Ok(mut signals) = Signals::new([SIGINT, SIGTERM, SIGHUP]) {
+            let _ = thread::spawn(move || {
+                if let Some(signal) = signals.forever().next() {
+                    restore();
+                    process::exit(128 + signal);
+                }
+            });
+        }
+        Terminal { input: Getch::new() }
+    }
+
+    // キー入力を待つ
+    pub fn getch(&self) -> io::Result<Key> {
+        self.input.getch()
+    }
+
+    // 元の画面に戻した後に標準出力に1行表示する
+    // 代替画面に表示した内容は戻すと消えるため、終了後に残したい結果はここに書く
+    pub fn println(&self, line: &str) {
+        if let Some(saved) = SAVED.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            saved.stdout.push_str(line);
+            saved.stdout.push('\n');
+        }
+    }
+
+    // 元の画面に戻した後に標準エラー出力に1行表示する
+    pub fn eprintln(&self, line: &str) {
+        if let Some(saved) = SAVED.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            saved.stderr.push_str(line);
+            saved.stderr.push('\n');
+        }
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+// 端末を`Terminal`の生成前の状態に戻す(2回目以降は何もしない)
+fn restore() {
+    let Some(saved) = SAVED.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+        return;
+    };
+    write_out("\x1b[0m\x1b[?25h\x1b[?1049l");
+    if let Some(termios) = saved.termios {
+        // SAFETY: `Terminal::enter`で取得した設定をそのまま書き戻すだけ
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) };
+    }
+    write_out(&saved.stdout);
+    io::stderr().write_all(saved.stderr.as_bytes()).ok();
+}
+
+// エスケープシーケンスをすぐに出力する
+fn write_out(seq: &str) {
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(seq.as_bytes()).ok();
+    stdout.flush().ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(detect(None, Some("truecolor"), Some("xterm-256color")), ColorMode::Truecolor);
+        assert_eq!(detect(None, Some("24bit"), Some("xterm")), ColorMode::Truecolor);
+        assert_eq!(detect(None, None, Some("xterm-256color")), ColorMode::Ansi256);
+        assert_eq!(detect(None, Some(""), Some("screen-256color")), ColorMode::Ansi256);
+        assert_eq!(detect(None, None, Some("linux")), ColorMode::Ansi16);
+        assert_eq!(detect(None, None, Some("dumb")), ColorMode::None);
+        assert_eq!(detect(None, None, None), ColorMode::None);
+        // `NO_COLOR`は他の環境変数より優先する(空の場合は無視する)
+        assert_eq!(detect(Some("1"), Some("truecolor"), Some("xterm-256color")), ColorMode::None);
+        assert_eq!(detect(Some(""), None, Some("xterm-256color")), ColorMode::Ansi256);
+    }
+}